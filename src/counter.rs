@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    ops::{Add, Sub},
+};
+
+/// A multiset, counts of zero are never stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter<T: Hash + Eq>(HashMap<T, usize>);
+
+impl<T: Hash + Eq> Default for Counter<T> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<T: Hash + Eq> Counter<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build from `(item, count)` pairs, same items are added up.
+    pub fn from_counts(iter: impl IntoIterator<Item = (T, usize)>) -> Self {
+        let mut counter = Self::new();
+        iter.into_iter()
+            .for_each(|(item, n)| counter.insert_n(item, n));
+        counter
+    }
+
+    pub fn insert(&mut self, item: T) {
+        self.insert_n(item, 1);
+    }
+
+    pub fn insert_n(&mut self, item: T, n: usize) {
+        if n > 0 {
+            *self.0.entry(item).or_default() += n;
+        }
+    }
+
+    /// Remove at most `n` of the item, returns how many are removed.
+    pub fn remove_n(&mut self, item: &T, n: usize) -> usize {
+        let Some(count) = self.0.get_mut(item) else {
+            return 0;
+        };
+        let removed = n.min(*count);
+        *count -= removed;
+        if *count == 0 {
+            self.0.remove(item);
+        }
+        removed
+    }
+
+    /// 0 if never seen.
+    pub fn get(&self, item: &T) -> usize {
+        self.0.get(item).copied().unwrap_or(0)
+    }
+
+    /// Count of distinct items.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sum of all counts.
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.0.iter().map(|(item, n)| (item, *n))
+    }
+
+    /// The `n` items with highest counts, from high to low. Order of ties is not specified.
+    pub fn most_common(&self, n: usize) -> Vec<(&T, usize)> {
+        let mut items = self.iter().collect::<Vec<_>>();
+        items.sort_unstable_by_key(|(_, n)| std::cmp::Reverse(*n));
+        items.truncate(n);
+        items
+    }
+
+    /// Minimum count of every item.
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Self(
+            self.0
+                .iter()
+                .filter_map(|(item, n)| {
+                    let m = other.get(item);
+                    (m > 0).then(|| (item.clone(), m.min(*n)))
+                })
+                .collect(),
+        )
+    }
+
+    /// Maximum count of every item.
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        let mut result = self.clone();
+        other.iter().for_each(|(item, n)| {
+            let count = result.0.entry(item.clone()).or_default();
+            *count = (*count).max(n);
+        });
+        result
+    }
+}
+
+impl<T: Hash + Eq> FromIterator<T> for Counter<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut counter = Self::new();
+        counter.extend(iter);
+        counter
+    }
+}
+
+impl<T: Hash + Eq> Extend<T> for Counter<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|item| self.insert(item));
+    }
+}
+
+impl<T: Hash + Eq> IntoIterator for Counter<T> {
+    type Item = (T, usize);
+    type IntoIter = std::collections::hash_map::IntoIter<T, usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Counts are added.
+impl<T: Hash + Eq> Add for Counter<T> {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self {
+        rhs.into_iter().for_each(|(item, n)| self.insert_n(item, n));
+        self
+    }
+}
+
+/// Counts are subtracted, items which go to zero or below are removed.
+impl<T: Hash + Eq> Sub for Counter<T> {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self {
+        rhs.into_iter().for_each(|(item, n)| {
+            self.remove_n(&item, n);
+        });
+        self
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{image::Grid, neighbors_limited};
+
+/// Union-find over `0..len`, with union by size and path compression.
+#[derive(Debug, Clone, Default)]
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    count: usize,
+}
+
+impl DisjointSet {
+    pub fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+            count: len,
+        }
+    }
+
+    /// Add a new single element set, returns its index.
+    pub fn push(&mut self) -> usize {
+        let idx = self.parent.len();
+        self.parent.push(idx);
+        self.size.push(1);
+        self.count += 1;
+        idx
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Number of disjoint sets.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut x = x;
+        while self.parent[x] != root {
+            x = std::mem::replace(&mut self.parent[x], root);
+        }
+        root
+    }
+
+    /// Returns `false` if they are already in the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        self.count -= 1;
+        true
+    }
+
+    pub fn same(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Size of the set which contains `x`.
+    pub fn size_of(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+/// Connected components of a grid, labels are numbered in row-major order of
+/// the first cell of each component.
+#[derive(Debug, Clone)]
+pub struct Components {
+    width: usize,
+    labels: Vec<usize>,
+    count: usize,
+}
+
+impl Components {
+    /// Two cells next to each other (up, down, left, right) are connected when `adjacent` says so.
+    pub fn label<G: Grid + ?Sized>(
+        grid: &G,
+        mut adjacent: impl FnMut(G::Cell, G::Cell) -> bool,
+    ) -> Self {
+        let (height, width) = grid.size();
+        let mut set = DisjointSet::new(height * width);
+        for row in 0..height {
+            for col in 0..width {
+                // Only right and down, the other two are checked from the neighbor
+                for (r, c) in neighbors_limited((row, col), (height, width))[1..]
+                    .iter()
+                    .step_by(2)
+                    .flatten()
+                {
+                    if adjacent(grid.cell((row, col)), grid.cell((*r, *c))) {
+                        set.union(row * width + col, r * width + c);
+                    }
+                }
+            }
+        }
+
+        let mut ids = vec![usize::MAX; height * width];
+        let mut count = 0;
+        let labels = (0..height * width)
+            .map(|idx| {
+                let root = set.find(idx);
+                if ids[root] == usize::MAX {
+                    ids[root] = count;
+                    count += 1;
+                }
+                ids[root]
+            })
+            .collect();
+
+        Self {
+            width,
+            labels,
+            count,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn get(&self, (row, col): (usize, usize)) -> usize {
+        self.labels[row * self.width + col]
+    }
+
+    /// Cells of every component, indexed by label.
+    pub fn groups(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut groups = vec![vec![]; self.count];
+        self.labels.iter().enumerate().for_each(|(idx, label)| {
+            groups[*label].push((idx / self.width, idx % self.width));
+        });
+        groups
+    }
+}
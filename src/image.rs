@@ -0,0 +1,239 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub type Rgb = (u8, u8, u8);
+
+/// Anything that looks like a 2D map, indexed by `(row, col)`.
+pub trait Grid {
+    type Cell;
+
+    /// `(height, width)`
+    fn size(&self) -> (usize, usize);
+
+    fn cell(&self, pos: (usize, usize)) -> Self::Cell;
+}
+
+/// All rows must have the same length.
+impl<T: Copy> Grid for [Vec<T>] {
+    type Cell = T;
+
+    fn size(&self) -> (usize, usize) {
+        let width = self.first().map_or(0, Vec::len);
+        assert!(self.iter().all(|row| row.len() == width), "ragged grid");
+        (self.len(), width)
+    }
+
+    fn cell(&self, (row, col): (usize, usize)) -> T {
+        self[row][col]
+    }
+}
+
+impl<T: Copy> Grid for Vec<Vec<T>> {
+    type Cell = T;
+
+    fn size(&self) -> (usize, usize) {
+        self.as_slice().size()
+    }
+
+    fn cell(&self, pos: (usize, usize)) -> T {
+        self.as_slice().cell(pos)
+    }
+}
+
+/// Pixel types which have a binary netpbm format:
+/// `bool` for P4 (`true` is black), `u8` for P5 and `Rgb` for P6.
+pub trait Pixel: Copy {
+    const MAGIC: &'static str;
+    const EXTENSION: &'static str;
+
+    fn write_header(width: usize, height: usize, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(writer, "{}\n{} {}\n", Self::MAGIC, width, height)
+    }
+
+    fn write_row(row: &[Self], writer: &mut impl Write) -> std::io::Result<()>;
+}
+
+impl Pixel for bool {
+    const MAGIC: &'static str = "P4";
+    const EXTENSION: &'static str = "pbm";
+
+    fn write_row(row: &[Self], writer: &mut impl Write) -> std::io::Result<()> {
+        let packed = row
+            .chunks(8)
+            .map(|bits| {
+                bits.iter()
+                    .enumerate()
+                    .filter(|(_, bit)| **bit)
+                    .fold(0u8, |acc, (n, _)| acc | (0x80 >> n))
+            })
+            .collect::<Vec<_>>();
+        writer.write_all(&packed)
+    }
+}
+
+impl Pixel for u8 {
+    const MAGIC: &'static str = "P5";
+    const EXTENSION: &'static str = "pgm";
+
+    fn write_header(width: usize, height: usize, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(writer, "{}\n{} {}\n255\n", Self::MAGIC, width, height)
+    }
+
+    fn write_row(row: &[Self], writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(row)
+    }
+}
+
+impl Pixel for Rgb {
+    const MAGIC: &'static str = "P6";
+    const EXTENSION: &'static str = "ppm";
+
+    fn write_header(width: usize, height: usize, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(writer, "{}\n{} {}\n255\n", Self::MAGIC, width, height)
+    }
+
+    fn write_row(row: &[Self], writer: &mut impl Write) -> std::io::Result<()> {
+        let bytes = row
+            .iter()
+            .flat_map(|(r, g, b)| [*r, *g, *b])
+            .collect::<Vec<_>>();
+        writer.write_all(&bytes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image<P> {
+    width: usize,
+    height: usize,
+    pixels: Vec<P>,
+}
+
+impl<P: Pixel> Image<P> {
+    pub fn new((height, width): (usize, usize), background: P) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    /// Map every cell of the grid to a pixel.
+    pub fn from_grid<G: Grid + ?Sized>(grid: &G, mut palette: impl FnMut(G::Cell) -> P) -> Self {
+        let (height, width) = grid.size();
+        let pixels = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|pos| palette(grid.cell(pos)))
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// `points` are `(row, col)`, in any order, out of range points are ignored.
+    pub fn from_points(
+        size: (usize, usize),
+        points: impl IntoIterator<Item = (usize, usize)>,
+        background: P,
+        foreground: P,
+    ) -> Self {
+        let mut image = Self::new(size, background);
+        points
+            .into_iter()
+            .for_each(|pos| image.set(pos, foreground));
+        image
+    }
+
+    /// `(height, width)`
+    pub fn size(&self) -> (usize, usize) {
+        (self.height, self.width)
+    }
+
+    pub fn get(&self, (row, col): (usize, usize)) -> Option<P> {
+        (row < self.height && col < self.width).then(|| self.pixels[row * self.width + col])
+    }
+
+    pub fn set(&mut self, (row, col): (usize, usize), pixel: P) {
+        if row < self.height && col < self.width {
+            self.pixels[row * self.width + col] = pixel;
+        }
+    }
+
+    /// Every pixel becomes a `factor * factor` square.
+    pub fn upscale(&self, factor: usize) -> Self {
+        let width = self.width * factor;
+        let height = self.height * factor;
+        let pixels = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row / factor, col / factor)))
+            .map(|(row, col)| self.pixels[row * self.width + col])
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        P::write_header(self.width, self.height, &mut writer)?;
+        if self.width > 0 {
+            for row in self.pixels.chunks(self.width) {
+                P::write_row(row, &mut writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The extension of the format is appended to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+        let mut path = path.as_ref().as_os_str().to_owned();
+        path.push(".");
+        path.push(P::EXTENSION);
+        let path = PathBuf::from(path);
+        let f = std::fs::File::create(&path)?;
+        let mut writer = std::io::BufWriter::new(f);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+/// Write images as `dir/{prefix}{number}.{ext}`, numbers are zero padded.
+#[derive(Debug)]
+pub struct FrameSequence {
+    dir: PathBuf,
+    prefix: String,
+    digits: usize,
+    next: usize,
+}
+
+impl FrameSequence {
+    pub fn new(
+        dir: impl AsRef<Path>,
+        prefix: impl Into<String>,
+        digits: usize,
+    ) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.into(),
+            digits,
+            next: 0,
+        })
+    }
+
+    /// Continue numbering from `n`.
+    pub fn start_at(mut self, n: usize) -> Self {
+        self.next = n;
+        self
+    }
+
+    pub fn push<P: Pixel>(&mut self, image: &Image<P>) -> std::io::Result<PathBuf> {
+        let name = format!("{}{:0width$}", self.prefix, self.next, width = self.digits);
+        self.next += 1;
+        image.save(self.dir.join(name))
+    }
+}
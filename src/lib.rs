@@ -1,4 +1,13 @@
-use std::io::{BufRead, Read};
+pub mod counter;
+pub mod dsu;
+pub mod image;
+pub mod num;
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io::{BufRead, Read},
+};
 
 fn open(filename: &str) -> impl BufRead {
     let f = std::fs::OpenOptions::new()
@@ -57,3 +66,11 @@ pub fn neighbors_limited(
     });
     result
 }
+
+/// Collect values with the same key together, values keep their order.
+pub fn group<K: Hash + Eq, V>(iter: impl IntoIterator<Item = (K, V)>) -> HashMap<K, Vec<V>> {
+    let mut map = HashMap::<K, Vec<V>>::new();
+    iter.into_iter()
+        .for_each(|(key, value)| map.entry(key).or_default().push(value));
+    map
+}
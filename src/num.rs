@@ -0,0 +1,152 @@
+use std::fmt::Debug;
+
+/// Primitive integers, only what the functions below need.
+pub trait Integer: Copy + Ord + Debug {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn checked_neg(self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+    fn checked_rem(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn checked_neg(self) -> Option<Self> {
+                <$t>::checked_neg(self)
+            }
+
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_mul(self, rhs)
+            }
+
+            fn checked_div(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_div(self, rhs)
+            }
+
+            fn checked_rem(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_rem(self, rhs)
+            }
+        }
+    )*};
+}
+
+impl_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Always non-negative, `gcd(0, 0)` is 0.
+/// `None` when the result does not fit, like `gcd(i64::MIN, 0)`.
+pub fn gcd<T: Integer>(mut a: T, mut b: T) -> Option<T> {
+    while b != T::ZERO {
+        // Only `MIN % -1` overflows, which is 0
+        let r = a.checked_rem(b).unwrap_or(T::ZERO);
+        (a, b) = (b, r);
+    }
+    if a < T::ZERO {
+        a.checked_neg()
+    } else {
+        Some(a)
+    }
+}
+
+/// Always non-negative, `lcm(0, x)` is 0.
+pub fn lcm<T: Integer>(a: T, b: T) -> Option<T> {
+    if a == T::ZERO || b == T::ZERO {
+        return Some(T::ZERO);
+    }
+    let l = a.checked_div(gcd(a, b)?)?.checked_mul(b)?;
+    if l < T::ZERO {
+        l.checked_neg()
+    } else {
+        Some(l)
+    }
+}
+
+/// Returns `(g, x, y)` that `a * x + b * y = g = gcd(a, b)`.
+pub fn ext_gcd(a: i128, b: i128) -> Option<(i128, i128, i128)> {
+    if b == 0 {
+        if a < 0 {
+            Some((a.checked_neg()?, -1, 0))
+        } else {
+            Some((a, 1, 0))
+        }
+    } else {
+        let (g, x, y) = ext_gcd(b, a.checked_rem_euclid(b)?)?;
+        Some((
+            g,
+            y,
+            x.checked_sub(a.checked_div_euclid(b)?.checked_mul(y)?)?,
+        ))
+    }
+}
+
+/// `x` in `0..m` that `a * x = 1 (mod m)`, `None` if `a` and `m` are not coprime.
+pub fn mod_inv(a: i128, m: i128) -> Option<i128> {
+    assert!(m > 0);
+    let (g, x, _) = ext_gcd(a.checked_rem_euclid(m)?, m)?;
+    (g == 1).then(|| x.rem_euclid(m))
+}
+
+/// Solve `x = a (mod m)` for every `(a, m)`, moduli do not need to be coprime.
+/// Returns `(x, l)` that all solutions are `x + k * l`, `x` is in `0..l`.
+pub fn crt(congruences: impl IntoIterator<Item = (i128, i128)>) -> Option<(i128, i128)> {
+    congruences
+        .into_iter()
+        .try_fold((0, 1), |(a1, m1), (a2, m2)| {
+            assert!(m2 > 0);
+            let g = gcd(m1, m2)?;
+            let diff = a2.checked_sub(a1)?;
+            if diff % g != 0 {
+                return None;
+            }
+            let n = m2 / g;
+            let t = (diff / g)
+                .rem_euclid(n)
+                .checked_mul(mod_inv(m1 / g, n)?)?
+                .rem_euclid(n);
+            let l = (m1 / g).checked_mul(m2)?;
+            let x = a1.checked_add(m1.checked_mul(t)?)?.rem_euclid(l);
+            Some((x, l))
+        })
+}
+
+/// `base ^ exp % m`, never overflows.
+pub fn mod_pow(base: u64, mut exp: u64, m: u64) -> u64 {
+    assert!(m > 0);
+    let m = m as u128;
+    let mut base = base as u128 % m;
+    let mut result = 1 % m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % m;
+        }
+        base = base * base % m;
+        exp >>= 1;
+    }
+    result as u64
+}
+
+/// Largest `r` that `r * r <= n`.
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method from above, never overflows because `x <= n / x + x` here
+    let mut x = 1 << (n.ilog2() / 2 + 1);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Count of decimal digits, 0 has one digit.
+pub fn digits(n: u128) -> u32 {
+    n.checked_ilog10().map_or(1, |d| d + 1)
+}
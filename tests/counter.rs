@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use advent2024::counter::*;
+
+    #[test]
+    fn counting() {
+        let mut counter: Counter<char> = "mississippi".chars().collect();
+        assert_eq!(counter.get(&'s'), 4);
+        assert_eq!(counter.get(&'x'), 0);
+        assert_eq!(counter.len(), 4);
+        assert_eq!(counter.total(), 11);
+
+        let common = counter.most_common(2);
+        assert_eq!(common.len(), 2);
+        assert!(common.iter().all(|(_, n)| *n == 4));
+        assert_eq!(counter.most_common(10).last(), Some(&(&'m', 1)));
+
+        counter.extend("mm".chars());
+        counter.insert_n('x', 0);
+        assert_eq!(counter.most_common(1)[0].1, 4);
+        assert_eq!(counter.get(&'m'), 3);
+        assert_eq!(counter.get(&'x'), 0);
+        assert_eq!(counter.remove_n(&'m', 5), 3);
+        assert_eq!(counter.get(&'m'), 0);
+        assert_eq!(counter.len(), 3);
+    }
+
+    #[test]
+    fn multiset() {
+        let a: Counter<u8> = [1, 1, 1, 2, 3].into_iter().collect();
+        let b = Counter::from_counts([(1, 2), (2, 2), (4, 1), (4, 1)]);
+        assert_eq!(b.get(&4), 2);
+
+        let sum = a.clone() + b.clone();
+        assert_eq!(sum, Counter::from_counts([(1, 5), (2, 3), (3, 1), (4, 2)]));
+
+        let diff = a.clone() - b.clone();
+        assert_eq!(diff, Counter::from_counts([(1, 1), (3, 1)]));
+
+        assert_eq!(a.intersection(&b), Counter::from_counts([(1, 2), (2, 1)]));
+        assert_eq!(
+            a.union(&b),
+            Counter::from_counts([(1, 3), (2, 2), (3, 1), (4, 2)])
+        );
+        assert!((a.clone() - a).is_empty());
+    }
+}
@@ -1,8 +1,6 @@
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
-
-    use advent2024::*;
+    use advent2024::{counter::Counter, *};
 
     fn data() -> impl Iterator<Item = (u32, u32)> {
         read_by_line("tests/data/day1.input.txt").filter_map(|line| {
@@ -36,19 +34,16 @@ mod test {
 
     #[test]
     fn part2() {
-        let mut m = HashMap::<u32, u32>::with_capacity(1024);
+        let mut m = Counter::new();
 
         let l1: Vec<u32> = data()
             .map(|(n1, n2)| {
-                *m.entry(n2).or_default() += 1;
+                m.insert(n2);
                 n1
             })
             .collect();
 
-        let result: u32 = l1
-            .into_iter()
-            .map(|n| n * m.get(&n).copied().unwrap_or_default())
-            .sum();
+        let result: u32 = l1.into_iter().map(|n| n * m.get(&n) as u32).sum();
 
         assert_eq!(result, 23655822);
     }
@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{hash_map::Entry, HashMap, VecDeque};
+    use std::{
+        collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+        ops::RangeInclusive,
+    };
 
     use advent2024::*;
 
@@ -69,7 +72,7 @@ mod tests {
                         .flatten()
                     {
                         let tile = self.0.get_mut(pos.0).unwrap().get_mut(pos.1).unwrap();
-                        if tile.height + 1 == height {
+                        if tile.height.checked_add(1) == Some(height) {
                             let entry = s.entry(pos);
                             if matches!(entry, Entry::Vacant(_)) {
                                 if height == 1 {
@@ -88,13 +91,140 @@ mod tests {
         }
     }
 
-    fn data() -> Map {
-        let map = read_by_line("tests/data/day10.input.txt")
-            .map(|s| s.trim_end().as_bytes().iter().map(|b| b - b'0').collect())
+    #[derive(Debug, Clone)]
+    struct StepRule {
+        // Allowed `next - current` height
+        delta: RangeInclusive<i16>,
+        start: u8,
+        end: u8,
+        diagonal: bool,
+    }
+
+    impl Default for StepRule {
+        fn default() -> Self {
+            Self {
+                delta: 1..=1,
+                start: 0,
+                end: 9,
+                diagonal: false,
+            }
+        }
+    }
+
+    // Heights which can not be stepped on, the `.` in examples
+    const IMPASSABLE: u8 = u8::MAX;
+
+    impl Map {
+        fn height(&self, (row, col): (usize, usize)) -> u8 {
+            self.0[row][col].height
+        }
+
+        fn starts<'a>(&'a self, rule: &'a StepRule) -> impl Iterator<Item = (usize, usize)> + 'a {
+            self.filter(|t| t.height != IMPASSABLE)
+                .filter(|(_, t)| t.height == rule.start)
+                .map(|(pos, _)| pos)
+        }
+
+        fn steps(&self, (row, col): (usize, usize), rule: &StepRule) -> Vec<(usize, usize)> {
+            let (height, width) = (self.0.len(), self.0[0].len());
+            let current = self.height((row, col)) as i16;
+            (-1..=1)
+                .flat_map(|dr| (-1..=1).map(move |dc| (dr, dc)))
+                .filter(|&(dr, dc): &(isize, isize)| {
+                    (dr, dc) != (0, 0) && (rule.diagonal || dr == 0 || dc == 0)
+                })
+                .filter_map(|(dr, dc)| {
+                    Some((row.checked_add_signed(dr)?, col.checked_add_signed(dc)?))
+                })
+                .filter(|&(r, c)| r < height && c < width && self.height((r, c)) != IMPASSABLE)
+                .filter(|&pos| rule.delta.contains(&(self.height(pos) as i16 - current)))
+                .collect()
+        }
+
+        // Every distinct trail from `head`, found one by one. If `head` is already at the end
+        // height, it is the only trail, same as `summits`.
+        pub fn trails<'a>(&'a self, head: (usize, usize), rule: &'a StepRule) -> Trails<'a> {
+            Trails {
+                map: self,
+                rule,
+                path: vec![],
+                stack: vec![vec![head]],
+            }
+        }
+
+        // Summits reachable from `head`
+        pub fn summits(&self, head: (usize, usize), rule: &StepRule) -> HashSet<(usize, usize)> {
+            let mut result = HashSet::new();
+            let mut s = HashSet::from([head]);
+            let mut q = VecDeque::from([head]);
+            while let Some(pos) = q.pop_front() {
+                if self.height(pos) == rule.end {
+                    result.insert(pos);
+                    continue;
+                }
+                for next in self.steps(pos, rule) {
+                    if s.insert(next) {
+                        q.push_back(next);
+                    }
+                }
+            }
+            result
+        }
+    }
+
+    // Depth first search, the path never visits a tile twice
+    struct Trails<'a> {
+        map: &'a Map,
+        rule: &'a StepRule,
+        path: Vec<(usize, usize)>,
+        stack: Vec<Vec<(usize, usize)>>,
+    }
+
+    impl Iterator for Trails<'_> {
+        type Item = Vec<(usize, usize)>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while let Some(candidates) = self.stack.last_mut() {
+                let Some(pos) = candidates.pop() else {
+                    self.stack.pop();
+                    self.path.pop();
+                    continue;
+                };
+                if self.path.contains(&pos) {
+                    continue;
+                }
+                if self.map.height(pos) == self.rule.end {
+                    let mut trail = self.path.clone();
+                    trail.push(pos);
+                    return Some(trail);
+                }
+                self.path.push(pos);
+                self.stack.push(self.map.steps(pos, self.rule));
+            }
+            None
+        }
+    }
+
+    fn parse(lines: impl Iterator<Item = String>) -> Map {
+        let map = lines
+            .map(|s| {
+                s.trim_end()
+                    .as_bytes()
+                    .iter()
+                    .map(|b| match b {
+                        b'.' => IMPASSABLE,
+                        b => b - b'0',
+                    })
+                    .collect()
+            })
             .collect();
         Map::new(map)
     }
 
+    fn data() -> Map {
+        parse(read_by_line("tests/data/day10.input.txt"))
+    }
+
     #[test]
     fn part1_and_2() {
         let map = data();
@@ -104,4 +234,100 @@ mod tests {
         assert_eq!(score, 816);
         assert_eq!(rating, 1960);
     }
+
+    const EXAMPLE: &str = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+
+    fn example(s: &str) -> Map {
+        parse(s.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn example_trails() {
+        let map = example(EXAMPLE);
+        let rule = StepRule::default();
+        let (score, rating) = map.trailheads().fold((0, 0), |(score, rating), tile| {
+            (score + tile.score, rating + tile.rating)
+        });
+        assert_eq!((score, rating), (36, 81));
+
+        let heads = map.starts(&rule).collect::<Vec<_>>();
+        assert_eq!(heads.len(), 9);
+        for head in heads {
+            let tile = &map.0[head.0][head.1];
+            let trails = map.trails(head, &rule).collect::<Vec<_>>();
+            let summits = map.summits(head, &rule);
+            assert_eq!(trails.len(), tile.rating);
+            assert_eq!(summits.len(), tile.score);
+            assert!(trails.iter().all(|trail| trail.len() == 10));
+            assert!(trails
+                .iter()
+                .all(|trail| summits.contains(trail.last().unwrap())));
+            assert_eq!(trails.iter().collect::<HashSet<_>>().len(), trails.len());
+        }
+
+        // Walk down from summits instead
+        let down = StepRule {
+            delta: -1..=-1,
+            start: 9,
+            end: 0,
+            ..Default::default()
+        };
+        let count: usize = map
+            .starts(&down)
+            .map(|top| map.trails(top, &down).count())
+            .sum();
+        assert_eq!(count, 81);
+    }
+
+    #[test]
+    fn custom_rules() {
+        let map = example("0.\n.1");
+        let mut rule = StepRule {
+            end: 1,
+            ..Default::default()
+        };
+        assert_eq!(map.trails((0, 0), &rule).count(), 0);
+        rule.diagonal = true;
+        assert_eq!(
+            map.trails((0, 0), &rule).collect::<Vec<_>>(),
+            [vec![(0, 0), (1, 1)]]
+        );
+
+        // Flat moves are allowed, so there are too many trails to list them all
+        let map = example("000000\n000000\n000000\n000000\n000001");
+        let rule = StepRule {
+            delta: 0..=1,
+            end: 1,
+            ..Default::default()
+        };
+        assert_eq!(map.trails((0, 0), &rule).take(1000).count(), 1000);
+        assert_eq!(map.summits((0, 0), &rule), HashSet::from([(4, 5)]));
+        assert!(map
+            .trails((0, 0), &rule)
+            .take(100)
+            .all(|trail| trail.last() == Some(&(4, 5))));
+
+        // Start is the end, the trail has no step
+        let map = example(
+            "00
+00",
+        );
+        let rule = StepRule {
+            delta: 0..=0,
+            end: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            map.trails((0, 0), &rule).collect::<Vec<_>>(),
+            [vec![(0, 0)]]
+        );
+        assert_eq!(map.summits((0, 0), &rule), HashSet::from([(0, 0)]));
+    }
 }
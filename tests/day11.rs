@@ -1,19 +1,41 @@
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, collections::HashMap};
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        fmt::Display,
+    };
 
     use advent2024::*;
 
-    fn data() -> Vec<u64> {
-        read_by_line("tests/data/day11.input.txt")
-            .next()
-            .unwrap()
-            .trim_end()
+    fn parse(line: &str) -> Vec<u64> {
+        line.trim_end()
             .split(' ')
             .map(|n| n.parse::<u64>().unwrap())
             .collect()
     }
 
+    fn data() -> Vec<u64> {
+        parse(&read_by_line("tests/data/day11.input.txt").next().unwrap())
+    }
+
+    fn blink(x: u64) -> (u64, Option<u64>) {
+        if x == 0 {
+            // Rule 1
+            (1, None)
+        } else {
+            let l = num::digits(x as u128);
+            if l.is_multiple_of(2) {
+                // Rule 2
+                let half = 10u64.pow(l / 2);
+                (x / half, Some(x % half))
+            } else {
+                // Rule 3
+                (x * 2024, None)
+            }
+        }
+    }
+
     // If uses rayon or other multi-thread calculation
     // need change this to LazyCell<Mutex<HashMap<...>>>
     thread_local! {
@@ -29,19 +51,9 @@ mod tests {
             return value;
         }
 
-        let result = if x == 0 {
-            // Rule 1
-            f(1, n - 1)
-        } else {
-            let s = x.to_string();
-            let l = s.len();
-            if l % 2 == 0 {
-                // Rule 2
-                f(s[0..l / 2].parse().unwrap(), n - 1) + f(s[l / 2..].parse().unwrap(), n - 1)
-            } else {
-                // Rule 3
-                f(x * 2024, n - 1)
-            }
+        let result = match blink(x) {
+            (a, None) => f(a, n - 1),
+            (a, Some(b)) => f(a, n - 1) + f(b, n - 1),
         };
 
         CACHE.with_borrow_mut(|cache| {
@@ -51,6 +63,164 @@ mod tests {
         result
     }
 
+    trait Count: Clone {
+        fn zero() -> Self;
+        fn one() -> Self;
+        fn add(&mut self, other: &Self);
+    }
+
+    impl Count for u128 {
+        fn zero() -> Self {
+            0
+        }
+
+        fn one() -> Self {
+            1
+        }
+
+        fn add(&mut self, other: &Self) {
+            *self += other;
+        }
+    }
+
+    // Little endian, every limb is 0..BASE, so it can be printed in decimal easily
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct BigUint(Vec<u64>);
+
+    impl BigUint {
+        const BASE: u64 = 1_000_000_000_000_000_000;
+
+        fn rem(&self, m: u64) -> u64 {
+            self.0.iter().rev().fold(0, |acc, limb| {
+                ((acc as u128 * Self::BASE as u128 + *limb as u128) % m as u128) as u64
+            })
+        }
+    }
+
+    impl Count for BigUint {
+        fn zero() -> Self {
+            Self(vec![])
+        }
+
+        fn one() -> Self {
+            Self(vec![1])
+        }
+
+        fn add(&mut self, other: &Self) {
+            let mut carry = 0;
+            for i in 0..self.0.len().max(other.0.len()) {
+                if i == self.0.len() {
+                    self.0.push(0);
+                }
+                let sum = self.0[i] + other.0.get(i).copied().unwrap_or(0) + carry;
+                self.0[i] = sum % Self::BASE;
+                carry = sum / Self::BASE;
+            }
+            if carry > 0 {
+                self.0.push(carry);
+            }
+        }
+    }
+
+    impl Display for BigUint {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let mut limbs = self.0.iter().rev();
+            write!(f, "{}", limbs.next().unwrap_or(&0))?;
+            limbs.try_for_each(|limb| write!(f, "{:018}", limb))
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Modular<const M: u64>(u64);
+
+    impl<const M: u64> Count for Modular<M> {
+        fn zero() -> Self {
+            Self(0)
+        }
+
+        fn one() -> Self {
+            Self(1 % M)
+        }
+
+        fn add(&mut self, other: &Self) {
+            self.0 = ((self.0 as u128 + other.0 as u128) % M as u128) as u64;
+        }
+    }
+
+    // Stone value -> how many stones have it, order of stones does not matter
+    #[derive(Debug, Clone)]
+    struct Stones<C> {
+        blinks: usize,
+        counts: HashMap<u64, C>,
+    }
+
+    impl<C: Count> Stones<C> {
+        fn new(initial: &[u64]) -> Self {
+            let mut counts: HashMap<u64, C> = HashMap::new();
+            initial.iter().for_each(|x| match counts.get_mut(x) {
+                Some(c) => c.add(&C::one()),
+                None => {
+                    counts.insert(*x, C::one());
+                }
+            });
+            Self { blinks: 0, counts }
+        }
+
+        fn blink(&mut self) {
+            let mut next: HashMap<u64, C> = HashMap::with_capacity(self.counts.len());
+            let mut put = |x, count: &C| match next.get_mut(&x) {
+                Some(c) => c.add(count),
+                None => {
+                    next.insert(x, count.clone());
+                }
+            };
+            for (x, count) in &self.counts {
+                let (a, b) = blink(*x);
+                put(a, count);
+                if let Some(b) = b {
+                    put(b, count);
+                }
+            }
+            self.counts = next;
+            self.blinks += 1;
+        }
+
+        fn after(mut self, n: usize) -> Self {
+            (0..n).for_each(|_| self.blink());
+            self
+        }
+
+        fn histogram(&self) -> &HashMap<u64, C> {
+            &self.counts
+        }
+
+        fn total(&self) -> C {
+            let mut total = C::zero();
+            self.counts.values().for_each(|c| total.add(c));
+            total
+        }
+    }
+
+    // All values stones can ever have, and after how many blinks no new one appears
+    fn distinct_values(initial: &[u64]) -> (usize, HashSet<u64>) {
+        let mut seen = initial.iter().copied().collect::<HashSet<_>>();
+        let mut frontier = seen.clone();
+        let mut blinks = 0usize;
+        while !frontier.is_empty() {
+            frontier = frontier
+                .into_iter()
+                .flat_map(|x| {
+                    let (a, b) = blink(x);
+                    Some(a).into_iter().chain(b)
+                })
+                .filter(|x| seen.insert(*x))
+                .collect();
+            blinks += 1;
+        }
+        // The last round found nothing new
+        (blinks.saturating_sub(1), seen)
+    }
+
     #[test]
     fn part1() {
         let count: usize = data().into_iter().map(|x| f(x, 25)).sum();
@@ -62,4 +232,58 @@ mod tests {
         let count: usize = data().into_iter().map(|x| f(x, 75)).sum();
         assert_eq!(count, 220377651399268);
     }
+
+    #[test]
+    fn example_histogram() {
+        let initial = parse("125 17");
+        let stones = Stones::<u128>::new(&initial).after(6);
+        assert_eq!(stones.total(), 22);
+        let histogram = stones.histogram();
+        assert_eq!(histogram.len(), 15);
+        assert_eq!(histogram[&2], 4);
+        assert_eq!(histogram[&0], 2);
+        assert_eq!(histogram[&2097446912], 1);
+
+        let count: usize = initial.iter().map(|x| f(*x, 25)).sum();
+        assert_eq!(count, 55312);
+        assert_eq!(Stones::<u128>::new(&initial).after(25).total(), 55312);
+        assert_eq!(
+            Stones::<u128>::new(&initial).after(75).total(),
+            65601038650482
+        );
+    }
+
+    #[test]
+    fn many_blinks() {
+        const M: u64 = 1_000_000_007;
+        let initial = parse("125 17");
+        let big = Stones::<BigUint>::new(&initial).after(75).total();
+        assert_eq!(big.to_string(), "65601038650482");
+
+        let big = Stones::<BigUint>::new(&initial).after(500).total();
+        let modular = Stones::<Modular<M>>::new(&initial).after(500).total();
+        assert!(big.to_string().len() > 80);
+        assert_eq!(big.rem(M), modular.0);
+
+        let mut sum = BigUint(vec![BigUint::BASE - 1, BigUint::BASE - 1]);
+        sum.add(&BigUint::one());
+        assert_eq!(sum.to_string(), format!("1{}", "0".repeat(36)));
+    }
+
+    #[test]
+    fn distinct_stones() {
+        let initial = parse("125 17");
+        let (blinks, values) = distinct_values(&initial);
+        // Nothing new shows up after that
+        let stones = Stones::<Modular<7>>::new(&initial).after(blinks + 50);
+        assert!(stones.histogram().keys().all(|x| values.contains(x)));
+
+        let (_, from_zero) = distinct_values(&[0]);
+        assert_eq!(from_zero.len(), 54);
+        assert!(from_zero.contains(&2024));
+
+        let (blinks, values) = distinct_values(&[]);
+        assert_eq!(blinks, 0);
+        assert!(values.is_empty());
+    }
 }
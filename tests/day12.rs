@@ -1,8 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::collections::{HashMap, HashSet};
 
-    use advent2024::*;
+    use advent2024::{
+        dsu::Components,
+        image::{Image, Rgb},
+        *,
+    };
 
     #[derive(Debug)]
     struct Garden {
@@ -12,94 +16,13 @@ mod tests {
     }
 
     impl Garden {
-        pub fn regions(self) -> GardenRegions {
-            GardenRegions {
-                checked: vec![vec![false; self.width]; self.height],
-                garden: self,
-                row: 0,
-                col: 0,
-            }
-        }
-    }
-
-    struct GardenRegions {
-        checked: Vec<Vec<bool>>,
-        garden: Garden,
-        row: usize,
-        col: usize,
-    }
-
-    impl GardenRegions {
-        fn move_to_next(&mut self) {
-            self.col += 1;
-            if self.col == self.garden.width {
-                self.row += 1;
-                self.col = 0;
-            }
-        }
-
-        fn move_to_next_start(&mut self) {
-            loop {
-                self.move_to_next();
-                if !self.current_checked() {
-                    return;
-                }
-            }
-        }
-
-        fn current_checked(&self) -> bool {
-            self.row < self.garden.height && self.checked[self.row][self.col]
-        }
-
-        fn current_plot(&self) -> u8 {
-            self.garden.plots[self.row][self.col]
-        }
-
-        fn current_region(&self) -> Region {
-            let plot = self.current_plot();
-
-            let mut region = HashSet::new();
-            let mut q = VecDeque::new();
-            let mut s = HashSet::new();
-
-            q.push_back((self.row, self.col));
-            s.insert((self.row, self.col));
-            while let Some((row, col)) = q.pop_front() {
-                if self.garden.plots[row][col] == plot {
-                    region.insert((row, col));
-                    neighbors_limited((row, col), (self.garden.height, self.garden.width))
-                        .into_iter()
-                        .flatten()
-                        .for_each(|pos| {
-                            if !self.checked[pos.0][pos.1] && !s.contains(&pos) {
-                                s.insert(pos);
-                                q.push_back(pos);
-                            }
-                        });
-                }
-            }
-
-            Region { plots: region }
-        }
-    }
-
-    impl Iterator for GardenRegions {
-        type Item = Region;
-
-        fn next(&mut self) -> Option<Self::Item> {
-            if self.row >= self.garden.height {
-                return None;
-            }
-
-            let region = self.current_region();
-
-            for (row, col) in region.plots.iter().copied() {
-                self.checked[row][col] = true;
-            }
-
-            self.move_to_next_start();
-
-            Some(region)
+        pub fn regions(self) -> impl Iterator<Item = Region> {
+            Components::label(&self.plots, |a, b| a == b)
+                .groups()
+                .into_iter()
+                .map(|plots| Region {
+                    plots: plots.into_iter().collect(),
+                })
         }
     }
 
@@ -107,7 +30,7 @@ mod tests {
         plots: HashSet<(usize, usize)>,
     }
 
-    #[derive(Debug, PartialEq, Eq, Hash)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     #[repr(usize)]
     enum SideDir {
         Left,
@@ -196,15 +119,166 @@ mod tests {
                 .map(|dir_fences| dir_side_count(dir_fences.into_values()))
                 .sum()
         }
+
+        // A side starts at every corner, count convex and concave corners around each plot
+        pub fn corner_count(&self) -> usize {
+            let has = |(row, col): (usize, usize), (dr, dc): (isize, isize)| {
+                let pos = (row.checked_add_signed(dr), col.checked_add_signed(dc));
+                matches!(pos, (Some(row), Some(col)) if self.plots.contains(&(row, col)))
+            };
+
+            self.plots
+                .iter()
+                .map(|&plot| {
+                    [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+                        .into_iter()
+                        .filter(|&(dr, dc)| {
+                            let (vertical, horizontal) = (has(plot, (dr, 0)), has(plot, (0, dc)));
+                            (!vertical && !horizontal)
+                                || (vertical && horizontal && !has(plot, (dr, dc)))
+                        })
+                        .count()
+                })
+                .sum()
+        }
+
+        // Inclusive, ((top, left), (bottom, right))
+        pub fn bounding_box(&self) -> ((usize, usize), (usize, usize)) {
+            let rows = self.plots.iter().map(|(row, _)| *row);
+            let cols = self.plots.iter().map(|(_, col)| *col);
+            (
+                (rows.clone().min().unwrap(), cols.clone().min().unwrap()),
+                (rows.max().unwrap(), cols.max().unwrap()),
+            )
+        }
+
+        // Boundary edges between grid points, directed so the region is on the right hand side
+        fn edges(&self) -> HashMap<(usize, usize), Vec<SideDir>> {
+            let mut result: HashMap<_, Vec<_>> = HashMap::new();
+            self.fences().for_each(|Fence(row, col, dir)| {
+                let (start, dir) = match dir {
+                    SideDir::Up => ((row, col), SideDir::Right),
+                    SideDir::Right => ((row, col + 1), SideDir::Down),
+                    SideDir::Down => ((row + 1, col + 1), SideDir::Left),
+                    SideDir::Left => ((row + 1, col), SideDir::Up),
+                };
+                result.entry(start).or_default().push(dir);
+            });
+            result
+        }
+
+        // Every boundary as a closed polygon of grid points, only corners are kept.
+        // The outer one goes clockwise and holes go counterclockwise.
+        pub fn outline(&self) -> Outline {
+            let mut edges = self.edges();
+            let mut loops = vec![];
+
+            while let Some((&start, _)) = edges.iter().find(|(_, dirs)| !dirs.is_empty()) {
+                let mut vertices = vec![];
+                let mut pos = start;
+                let mut dir = edges.get_mut(&start).unwrap().pop().unwrap();
+                let first_dir = dir;
+                loop {
+                    pos = dir.step(pos);
+                    let outs = edges.get_mut(&pos).unwrap();
+                    // Turn left first, so loops touching at a point are split
+                    let next = [dir.left(), dir, dir.right()]
+                        .into_iter()
+                        .find(|d| (pos == start && *d == first_dir) || outs.contains(d))
+                        .unwrap();
+                    if next != dir {
+                        vertices.push(pos);
+                    }
+                    if pos == start && next == first_dir {
+                        break;
+                    }
+                    outs.retain(|d| *d != next);
+                    dir = next;
+                }
+                loops.push(vertices);
+            }
+
+            let (outer, holes): (Vec<_>, Vec<_>) = loops
+                .into_iter()
+                .partition(|vertices| signed_area(vertices) > 0);
+
+            Outline {
+                outer: outer.into_iter().next().unwrap(),
+                holes,
+            }
+        }
     }
 
-    fn data() -> Garden {
-        let plots = read_by_line("tests/data/day12.input.txt")
-            .map(|line| {
-                let mut line = line.into_bytes();
-                line.pop(); // remove \n
-                line
-            })
+    impl SideDir {
+        fn step(&self, (row, col): (usize, usize)) -> (usize, usize) {
+            match self {
+                Self::Left => (row, col - 1),
+                Self::Right => (row, col + 1),
+                Self::Up => (row - 1, col),
+                Self::Down => (row + 1, col),
+            }
+        }
+
+        fn left(&self) -> Self {
+            match self {
+                Self::Left => Self::Down,
+                Self::Right => Self::Up,
+                Self::Up => Self::Left,
+                Self::Down => Self::Right,
+            }
+        }
+
+        fn right(&self) -> Self {
+            match self {
+                Self::Left => Self::Up,
+                Self::Right => Self::Down,
+                Self::Up => Self::Right,
+                Self::Down => Self::Left,
+            }
+        }
+    }
+
+    // Twice the area, positive when clockwise on screen
+    fn signed_area(vertices: &[(usize, usize)]) -> isize {
+        vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .map(|(&(y1, x1), &(y2, x2))| (x1 * y2) as isize - (x2 * y1) as isize)
+            .sum()
+    }
+
+    #[derive(Debug)]
+    struct Outline {
+        outer: Vec<(usize, usize)>,
+        holes: Vec<Vec<(usize, usize)>>,
+    }
+
+    impl Outline {
+        fn vertex_count(&self) -> usize {
+            self.outer.len() + self.holes.iter().map(Vec::len).sum::<usize>()
+        }
+    }
+
+    // Each region in its own color
+    fn region_image(garden: Garden) -> Image<Rgb> {
+        let mut image = Image::new((garden.height, garden.width), (0, 0, 0));
+        for (idx, region) in garden.regions().enumerate() {
+            let color = (
+                (idx * 97 % 256) as u8,
+                ((idx * 57 + 80) % 256) as u8,
+                ((idx * 151 + 160) % 256) as u8,
+            );
+            region
+                .plots
+                .into_iter()
+                .for_each(|pos| image.set(pos, color));
+        }
+        image
+    }
+
+    fn parse(lines: impl Iterator<Item = String>) -> Garden {
+        let plots = lines
+            .map(|line| line.trim_end().as_bytes().to_vec())
             .collect::<Vec<_>>();
 
         Garden {
@@ -214,6 +288,10 @@ mod tests {
         }
     }
 
+    fn data() -> Garden {
+        parse(read_by_line("tests/data/day12.input.txt"))
+    }
+
     #[test]
     fn part1() {
         let garden = data();
@@ -229,4 +307,88 @@ mod tests {
 
         assert_eq!(result, 821428)
     }
+
+    fn example(s: &str) -> Garden {
+        parse(s.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn example_regions() {
+        let garden = example("AAAA\nBBCD\nBBCC\nEEEC");
+        let regions = garden.regions().collect::<Vec<_>>();
+        assert_eq!(regions.len(), 5);
+        assert_eq!(
+            regions
+                .iter()
+                .map(|x| x.area() * x.perimeter())
+                .sum::<usize>(),
+            140
+        );
+        assert_eq!(
+            regions
+                .iter()
+                .map(|x| x.area() * x.side_count())
+                .sum::<usize>(),
+            80
+        );
+
+        let image = region_image(example("AAAA\nBBCD\nBBCC\nEEEC"));
+        let colors = (0..4)
+            .flat_map(|row| (0..4).map(move |col| (row, col)))
+            .map(|pos| image.get(pos).unwrap())
+            .collect::<HashSet<_>>();
+        assert_eq!(colors.len(), 5);
+        assert_eq!(image.get((1, 0)), image.get((2, 1)));
+        assert_ne!(image.get((1, 3)), image.get((1, 2)));
+    }
+
+    #[test]
+    fn example_outlines() {
+        for (input, price) in [
+            ("AAAA\nBBCD\nBBCC\nEEEC", 80),
+            ("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO", 436),
+            ("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE", 236),
+            ("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA", 368),
+        ] {
+            let mut result = 0;
+            for region in example(input).regions() {
+                let outline = region.outline();
+                assert_eq!(region.corner_count(), region.side_count());
+                assert_eq!(outline.vertex_count(), region.side_count());
+                result += region.area() * region.corner_count();
+            }
+            assert_eq!(result, price);
+        }
+    }
+
+    #[test]
+    fn outline_geometry() {
+        let regions = example("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA")
+            .regions()
+            .collect::<Vec<_>>();
+        let a = regions.iter().find(|r| r.area() == 28).unwrap();
+        let outline = a.outline();
+        assert_eq!(a.bounding_box(), ((0, 0), (5, 5)));
+        assert_eq!(outline.outer.len(), 4);
+        assert_eq!(signed_area(&outline.outer), 2 * 36);
+        // Two holes touching at one point are still two holes
+        assert_eq!(outline.holes.len(), 2);
+        assert!(outline.holes.iter().all(|hole| hole.len() == 4));
+        assert!(outline.holes.iter().all(|hole| signed_area(hole) == -2 * 4));
+        assert!(outline.holes.iter().any(|hole| hole.contains(&(3, 3))));
+
+        let b = regions.iter().find(|r| r.plots.contains(&(3, 1))).unwrap();
+        assert_eq!(b.bounding_box(), ((3, 1), (4, 2)));
+        let mut outer = b.outline().outer;
+        outer.sort();
+        assert_eq!(outer, [(3, 1), (3, 3), (5, 1), (5, 3)]);
+
+        // Hole touches the outside at a point
+        let ring = example("AAA\nABA\nAAB").regions().next().unwrap();
+        let outline = ring.outline();
+        assert_eq!(outline.holes.len(), 1);
+        assert_eq!(outline.outer.len(), 6);
+        assert_eq!(ring.corner_count(), 10);
+        assert_eq!(ring.side_count(), 10);
+    }
 }
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering;
+
     use advent2024::*;
 
     #[derive(Debug)]
@@ -12,114 +14,115 @@ mod tests {
         by: i64,
     }
 
+    // Smallest s that c + k * s >= 0 and largest one, `None` means no bound
+    fn bound(c: i128, k: i128) -> Option<(Option<i128>, Option<i128>)> {
+        match k.signum() {
+            1 => Some((Some(-c.div_euclid(k)), None)),
+            -1 => Some((None, Some(c.div_euclid(-k)))),
+            _ => (c >= 0).then_some((None, None)),
+        }
+    }
+
     impl ClawMachine {
-        // Maybe the input data do not have any X=0 or Y=0, so I'm not sure if I
-        // handles those case properly, but I did my best...
         fn solve(&self) -> Option<(i64, i64)> {
-            // If target is zero, answer is always zero
-            if self.tx == 0 && self.ty == 0 {
-                return Some((0, 0));
-            }
+            self.cheapest((3, 1))
+        }
 
-            let ab = self.ax * self.by - self.ay * self.bx;
-            let at = self.ax * self.ty - self.ay * self.tx;
-            let bt = self.by * self.tx - self.bx * self.ty;
+        // Presses of A and B that reach the target with lowest `cost.0 * a + cost.1 * b`.
+        fn cheapest(&self, (cost_a, cost_b): (i64, i64)) -> Option<(i64, i64)> {
+            assert!(cost_a >= 0 && cost_b >= 0);
+            let [tx, ty, ax, ay, bx, by, cost_a, cost_b] = [
+                self.tx, self.ty, self.ax, self.ay, self.bx, self.by, cost_a, cost_b,
+            ]
+            .map(i128::from);
 
-            // A B is same direction
-            if ab == 0 {
-                // but target is not in this direction
-                if at != 0 || bt != 0 {
+            let ab = ax.checked_mul(by)?.checked_sub(ay.checked_mul(bx)?)?;
+            let (a, b) = if ab != 0 {
+                // Cramer's rule, only one solution
+                let at = ax.checked_mul(ty)?.checked_sub(ay.checked_mul(tx)?)?;
+                let bt = by.checked_mul(tx)?.checked_sub(bx.checked_mul(ty)?)?;
+                if at % ab != 0 || bt % ab != 0 {
                     return None;
                 }
+                (bt / ab, at / ab)
+            } else {
+                Self::collinear((tx, ty), (ax, ay), (bx, by), (cost_a, cost_b))?
+            };
 
-                // both A B is zero
-                if self.ax == 0 && self.ay == 0 && self.bx == 0 && self.by == 0 {
+            (a >= 0 && b >= 0).then_some(())?;
+            Some((a.try_into().ok()?, b.try_into().ok()?))
+        }
+
+        // A, B and target are all on a line, solve `ka * a + kb * b = t` on it.
+        fn collinear(
+            (tx, ty): (i128, i128),
+            (ax, ay): (i128, i128),
+            (bx, by): (i128, i128),
+            (cost_a, cost_b): (i128, i128),
+        ) -> Option<(i128, i128)> {
+            // Primitive direction of the line
+            let (dx, dy) = if (ax, ay) != (0, 0) {
+                (ax, ay)
+            } else {
+                (bx, by)
+            };
+            if (dx, dy) == (0, 0) {
+                return ((tx, ty) == (0, 0)).then_some((0, 0));
+            }
+            let g = num::gcd(dx, dy)?;
+            let (dx, dy) = (dx / g, dy / g);
+
+            let scale = |x: i128, y: i128| -> Option<i128> {
+                if x.checked_mul(dy)? != y.checked_mul(dx)? {
                     return None;
                 }
+                Some(if dx != 0 { x / dx } else { y / dy })
+            };
+            let t = scale(tx, ty)?;
+            let ka = scale(ax, ay)?;
+            let kb = scale(bx, by)?;
 
-                // if A is zero
-                if self.ax == 0 && self.ay == 0 {
-                    if self.bx != 0 && self.tx % self.bx == 0 {
-                        return Some((0, self.tx / self.bx));
-                    } else if self.by != 0 && self.ty % self.by == 0 {
-                        return Some((0, self.ty / self.by));
-                    } else {
-                        return None;
-                    }
-                }
+            let (g, x, y) = num::ext_gcd(ka, kb)?;
+            if t % g != 0 {
+                return None;
+            }
+            // a = a0 + p * s, b = b0 - q * s
+            let (a0, b0) = (x.checked_mul(t / g)?, y.checked_mul(t / g)?);
+            let (p, q) = (kb / g, ka / g);
 
-                // if B is zero
-                if self.bx == 0 && self.by == 0 {
-                    if self.ax != 0 && self.tx % self.ax == 0 {
-                        return Some((0, self.tx / self.ax));
-                    } else if self.ay != 0 && self.ty % self.ay == 0 {
-                        return Some((0, self.ty / self.ay));
-                    } else {
-                        return None;
-                    }
+            let (lo1, hi1) = bound(a0, p)?;
+            let (lo2, hi2) = bound(b0, -q)?;
+            let lo = lo1.into_iter().chain(lo2).max();
+            let hi = hi1.into_iter().chain(hi2).min();
+            if let (Some(lo), Some(hi)) = (lo, hi) {
+                if lo > hi {
+                    return None;
                 }
+            }
 
-                // No one is zero
-
-                // b cheap then a
-                if 9 * (self.bx * self.bx + self.by * self.by)
-                    > (self.ax * self.ax + self.ay * self.ay)
-                {
-                    if let Some(a) = (0..)
-                        .take_while(|a| self.tx - a * self.ax > 0 && self.ty - a * self.ay > 0)
-                        .find(|a| {
-                            (self.bx == 0 || (self.tx - a * self.ax) % self.bx == 0)
-                                && (self.by == 0 || (self.ty - a * self.ay) % self.by == 0)
-                        })
-                    {
-                        return Some((
-                            a,
-                            if self.bx == 0 {
-                                (self.tx - a * self.ay) / self.by
-                            } else {
-                                (self.ty - a * self.ax) / self.bx
-                            },
-                        ));
-                    }
-                } else {
-                    // a cheap then b
-                    if let Some(b) = (0..)
-                        .take_while(|b| self.tx - b * self.bx > 0 && self.ty - b * self.by > 0)
-                        .find(|b| {
-                            (self.ax == 0 || (self.tx - b * self.bx) % self.ax == 0)
-                                && (self.ay == 0 || (self.ty - b * self.by) % self.ay == 0)
-                        })
-                    {
-                        return Some((
-                            if self.ax == 0 {
-                                (self.ty - b * self.by) / self.ay
-                            } else {
-                                (self.tx - b * self.bx) / self.ax
-                            },
-                            b,
-                        ));
-                    }
+            // Cost is linear in s, so the best is one of the ends
+            let slope = cost_a.checked_mul(p)?.checked_sub(cost_b.checked_mul(q)?)?;
+            let s = match slope.cmp(&0) {
+                Ordering::Greater => lo,
+                Ordering::Less => hi,
+                // Any s in the bounds, which may both be open
+                Ordering::Equal => {
+                    let s = lo.map_or(0, |lo| lo.max(0));
+                    Some(hi.map_or(s, |hi| s.min(hi)))
                 }
+            }?;
 
-                None
-            } else {
-                let b = at / ab;
-                let a = bt / ab;
-                if a >= 0
-                    && b >= 0
-                    && self.ax * a + self.bx * b == self.tx
-                    && self.ay * a + self.by * b == self.ty
-                {
-                    Some((a, b))
-                } else {
-                    None
-                }
-            }
+            Some((
+                a0.checked_add(p.checked_mul(s)?)?,
+                b0.checked_sub(q.checked_mul(s)?)?,
+            ))
         }
     }
 
-    fn data(extra: i64) -> impl Iterator<Item = ClawMachine> {
-        let mut iter = read_by_line("tests/data/day13.input.txt");
+    fn parse(
+        mut iter: impl Iterator<Item = String>,
+        extra: i64,
+    ) -> impl Iterator<Item = ClawMachine> {
         std::iter::from_fn(move || {
             fn xy(line: String) -> (i64, i64) {
                 let mut it = line.trim().split([':', ' ', ',']);
@@ -150,6 +153,10 @@ mod tests {
         })
     }
 
+    fn data(extra: i64) -> impl Iterator<Item = ClawMachine> {
+        parse(read_by_line("tests/data/day13.input.txt"), extra)
+    }
+
     #[test]
     fn part1() {
         let result: i64 = data(0)
@@ -170,4 +177,112 @@ mod tests {
 
         assert_eq!(result, 83232379451012);
     }
+
+    const EXAMPLE: &str = "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279
+";
+
+    #[test]
+    fn example() {
+        let machines = |extra| parse(EXAMPLE.lines().map(str::to_string), extra);
+        let solved = machines(0).map(|m| m.solve()).collect::<Vec<_>>();
+        assert_eq!(solved, [Some((80, 40)), None, Some((38, 86)), None]);
+
+        let solved = machines(10000000000000)
+            .map(|m| m.solve().is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(solved, [false, true, false, true]);
+    }
+
+    fn machine(a: (i64, i64), b: (i64, i64), t: (i64, i64)) -> ClawMachine {
+        ClawMachine {
+            ax: a.0,
+            ay: a.1,
+            bx: b.0,
+            by: b.1,
+            tx: t.0,
+            ty: t.1,
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        let m = machine((1, 1), (2, 2), (10, 10));
+        assert_eq!(m.cheapest((3, 1)), Some((0, 5)));
+        assert_eq!(m.cheapest((1, 3)), Some((10, 0)));
+        assert_eq!(machine((2, 2), (3, 3), (7, 7)).solve(), Some((2, 1)));
+        assert_eq!(machine((2, 2), (4, 4), (3, 3)).solve(), None);
+        assert_eq!(machine((1, 1), (2, 2), (3, 4)).solve(), None);
+
+        // Zero buttons
+        assert_eq!(machine((0, 0), (2, 4), (4, 8)).solve(), Some((0, 2)));
+        assert_eq!(machine((3, 6), (0, 0), (9, 18)).solve(), Some((3, 0)));
+        assert_eq!(machine((0, 0), (0, 0), (0, 0)).solve(), Some((0, 0)));
+        assert_eq!(machine((0, 0), (0, 0), (1, 0)).solve(), None);
+
+        // Buttons in the opposite directions
+        assert_eq!(machine((1, 2), (-1, -2), (3, 6)).solve(), Some((3, 0)));
+        assert_eq!(machine((1, 2), (-1, -2), (-3, -6)).solve(), Some((0, 3)));
+        assert_eq!(machine((3, 0), (-2, 0), (1, 0)).solve(), Some((1, 1)));
+        // Equal costs, and free presses which make every solution the cheapest
+        for (a, b, t, cost) in [
+            ((1, 1), (-1, -1), (3, 3), (1, 1)),
+            ((1, 1), (-1, -1), (0, 0), (2, 2)),
+            ((1, 1), (-1, -1), (3, 3), (0, 0)),
+            ((2, 0), (-3, 0), (1, 0), (0, 0)),
+        ] {
+            let m = machine(a, b, t);
+            let (pa, pb) = m.cheapest(cost).unwrap();
+            assert!(pa >= 0 && pb >= 0);
+            assert_eq!((pa * a.0 + pb * b.0, pa * a.1 + pb * b.1), t);
+        }
+
+        // Part 2 sized numbers
+        let big = 10000000000000;
+        assert_eq!(
+            machine((3, 5), (6, 10), (big * 3, big * 5)).solve(),
+            Some((0, big / 2))
+        );
+        assert_eq!(machine((i64::MAX, 1), (1, 1), (1, 1)).solve(), Some((0, 1)));
+    }
+
+    #[test]
+    fn brute_force() {
+        for ax in 0..3 {
+            for ay in 0..3 {
+                for (bx, by) in [(0, 0), (1, 1), (2, 2), (ax, ay), (ax * 2, ay * 2), (1, 2)] {
+                    for t in [(0, 0), (4, 4), (6, 6), (3, 6), (5, 7)] {
+                        for cost in [(3, 1), (1, 3), (2, 2)] {
+                            let m = machine((ax, ay), (bx, by), t);
+                            let expected = (0..=12)
+                                .flat_map(|a| (0..=12).map(move |b| (a, b)))
+                                .filter(|(a, b)| a * ax + b * bx == t.0 && a * ay + b * by == t.1)
+                                .min_by_key(|(a, b)| (cost.0 * a + cost.1 * b, *a));
+                            let result = m.cheapest(cost);
+                            assert_eq!(
+                                result.map(|(a, b)| cost.0 * a + cost.1 * b),
+                                expected.map(|(a, b)| cost.0 * a + cost.1 * b),
+                                "{:?} {:?}",
+                                m,
+                                cost
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
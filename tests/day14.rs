@@ -1,8 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, io::Write, path::Path};
+    use std::{cmp::Reverse, collections::HashSet};
 
-    use advent2024::*;
+    use advent2024::{image::Image, *};
 
     #[derive(Debug, Clone)]
     struct Robot {
@@ -55,20 +55,28 @@ mod tests {
         read_by_line(path).filter_map(|line| line.parse().ok())
     }
 
-    fn safe_factor(
-        iter: impl IntoIterator<Item = Robot>,
+    fn positions<'a>(
+        robots: impl IntoIterator<Item = &'a Robot>,
         step: usize,
         size: (usize, usize),
-    ) -> usize {
-        let halfx = size.0 / 2;
-        let halfy = size.1 / 2;
-
-        iter.into_iter()
+    ) -> Vec<(usize, usize)> {
+        robots
+            .into_iter()
+            .cloned()
             .map(|mut robot| {
                 robot.go(step, size);
                 robot.pos
             })
-            .fold([0; 4], |mut quadrant, (x, y)| {
+            .collect()
+    }
+
+    fn quadrant_score(points: &[(usize, usize)], size: (usize, usize)) -> usize {
+        let halfx = size.0 / 2;
+        let halfy = size.1 / 2;
+
+        points
+            .iter()
+            .fold([0; 4], |mut quadrant, &(x, y)| {
                 if x != halfx && y != halfy {
                     let qx = if x < halfx { 0 } else { 1 } + if y < halfy { 0 } else { 2 };
                     quadrant[qx] += 1;
@@ -79,6 +87,107 @@ mod tests {
             .product()
     }
 
+    fn safe_factor(
+        iter: impl IntoIterator<Item = Robot>,
+        step: usize,
+        size: (usize, usize),
+    ) -> usize {
+        let robots: Vec<_> = iter.into_iter().collect();
+        quadrant_score(&positions(&robots, step, size), size)
+    }
+
+    fn variance(values: impl Iterator<Item = usize> + Clone) -> f64 {
+        let n = values.clone().count().max(1) as f64;
+        let mean = values.clone().sum::<usize>() as f64 / n;
+        values.map(|v| (v as f64 - mean).powi(2)).sum::<f64>() / n
+    }
+
+    fn largest_cluster(points: &[(usize, usize)]) -> usize {
+        let mut remain: HashSet<_> = points.iter().copied().collect();
+        let mut result = 0;
+        while let Some(&start) = remain.iter().next() {
+            remain.remove(&start);
+            let mut stack = vec![start];
+            let mut count = 0;
+            while let Some(pos) = stack.pop() {
+                count += 1;
+                for next in neighbors(pos).into_iter().flatten() {
+                    if remain.remove(&next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            result = result.max(count);
+        }
+        result
+    }
+
+    #[derive(Debug)]
+    struct StepStats {
+        var_x: f64,
+        var_y: f64,
+        quadrant_score: usize,
+        largest_cluster: usize,
+    }
+
+    fn stats(robots: &[Robot], step: usize, size: (usize, usize)) -> StepStats {
+        let points = positions(robots, step, size);
+        StepStats {
+            var_x: variance(points.iter().map(|(x, _)| *x)),
+            var_y: variance(points.iter().map(|(_, y)| *y)),
+            quadrant_score: quadrant_score(&points, size),
+            largest_cluster: largest_cluster(&points),
+        }
+    }
+
+    fn render(points: &[(usize, usize)], (width, height): (usize, usize)) -> String {
+        let points: HashSet<_> = points.iter().copied().collect();
+        (0..height)
+            .flat_map(|y| {
+                (0..width)
+                    .map(move |x| (x, y))
+                    .map(|pos| if points.contains(&pos) { '#' } else { '.' })
+                    .chain(Some('\n'))
+            })
+            .collect()
+    }
+
+    // x and y coordinates repeat independently every `width` and `height` steps, so find the
+    // most concentrated steps of each axis and combine them. Among the combinations the picture
+    // is the one with the largest cluster, and it sits off center so the quadrant score is low.
+    fn easter_egg(robots: &[Robot], size: (usize, usize)) -> (usize, String) {
+        let best = |period: usize, axis: fn(&(usize, usize)) -> usize| {
+            let mut steps = (0..period)
+                .map(|step| {
+                    (
+                        step,
+                        variance(positions(robots, step, size).iter().map(axis)),
+                    )
+                })
+                .collect::<Vec<_>>();
+            steps.sort_by(|a, b| a.1.total_cmp(&b.1));
+            steps.truncate(2);
+            steps
+        };
+        let xs = best(size.0, |(x, _)| *x);
+        let ys = best(size.1, |(_, y)| *y);
+        let step = xs
+            .iter()
+            .flat_map(|(tx, _)| ys.iter().map(move |(ty, _)| (*tx, *ty)))
+            .map(|(tx, ty)| {
+                let (step, _) =
+                    num::crt([(tx as i128, size.0 as i128), (ty as i128, size.1 as i128)]).unwrap();
+                step as usize
+            })
+            .max_by_key(|step| {
+                let stats = stats(robots, *step, size);
+                (stats.largest_cluster, Reverse(stats.quadrant_score))
+            })
+            .unwrap();
+
+        (step, render(&positions(robots, step, size), size))
+    }
+
     #[test]
     fn part1() {
         assert_eq!(
@@ -87,72 +196,120 @@ mod tests {
         );
     }
 
-    // This will create 10000 ppm file in your tests/data/day14 folder.
-    // So I comment the test attribute bellow, so it will not run by default
-
-    // #[test]
+    #[test]
     fn part2() {
         let robots: Vec<_> = data("tests/data/day14.input.txt").collect();
+        let size = (101, 103);
 
-        let dir = Path::new("tests/data/day14");
-        std::fs::create_dir_all(dir).unwrap();
-
-        fn write_ppm(
-            dir: impl AsRef<Path>,
-            filename: impl std::fmt::Display,
-            (height, width): (usize, usize),
-            points: impl IntoIterator<Item = (usize, usize)>,
-        ) {
-            let f: std::fs::File = std::fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(dir.as_ref().join(format!("{}.ppm", filename)))
-                .unwrap();
-            let mut writer = std::io::BufWriter::new(f);
-
-            writer.write_all(b"P3\n").unwrap();
-            writer
-                .write_fmt(format_args!("{} {}\n1\n", width, height))
-                .unwrap();
-
-            let mut cy = 0;
-            let mut cx = 0;
-            for (y, x) in points.into_iter().chain(Some((height, 0))) {
-                while cy != y {
-                    writer
-                        .write_all(" 0 0 0".repeat(width - cx).as_bytes())
-                        .unwrap();
-                    writer.write_all(b"\n").unwrap();
-                    cy += 1;
-                    cx = 0;
-                }
-                if y >= height {
-                    break;
-                }
-                if cx != x {
-                    writer
-                        .write_all(" 0 0 0".repeat(x - cx).as_bytes())
-                        .unwrap();
-                }
-                writer.write_all(b" 1 1 1").unwrap();
-                cx = x + 1
-            }
-        }
+        let (step, frame) = easter_egg(&robots, size);
+        assert!(step < size.0 * size.1);
+        assert!(frame.contains("##########"));
 
-        (1..10000).for_each(|step| {
-            let points: HashSet<_> = robots
-                .iter()
-                .cloned()
-                .map(|mut x| {
-                    x.go(step, (101, 103));
-                    x.pos
-                })
-                .map(|(x, y)| (y, x))
-                .collect();
-            let mut points: Vec<_> = points.into_iter().collect();
-            points.sort();
-            write_ppm(dir, step, (103, 101), points);
-        });
+        let egg = stats(&robots, step, size);
+        let normal = stats(&robots, step + 1, size);
+        assert!(egg.largest_cluster > normal.largest_cluster * 5);
+        assert!(egg.var_x < normal.var_x / 2.0 && egg.var_y < normal.var_y / 2.0);
+
+        let points = positions(&robots, step, size)
+            .into_iter()
+            .map(|(x, y)| (y, x))
+            .collect::<HashSet<_>>();
+        let image =
+            Image::from_points((size.1, size.0), points.iter().copied(), false, true).upscale(4);
+        assert_eq!(image.size(), (size.1 * 4, size.0 * 4));
+        let lit = (0..size.1 * 4)
+            .flat_map(|row| (0..size.0 * 4).map(move |col| (row, col)))
+            .filter(|pos| image.get(*pos) == Some(true))
+            .count();
+        assert_eq!(lit, points.len() * 16);
+    }
+
+    const EXAMPLE: &str = "p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3";
+
+    #[test]
+    fn example_safe_factor() {
+        let robots = EXAMPLE.lines().map(|line| line.parse::<Robot>().unwrap());
+        assert_eq!(safe_factor(robots, 100, (11, 7)), 12);
+    }
+
+    #[test]
+    fn detect_hidden_picture() {
+        let size = (101, 103);
+        let target = 6789;
+        let mut seed: u64 = 2024;
+        let mut random = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+
+        // A filled square at the target step, and some noise
+        let picture = (40..60).flat_map(|x| (30..50).map(move |y| (x, y)));
+        let noise = (0..100)
+            .map(|_| (random(size.0), random(size.1)))
+            .collect::<Vec<_>>();
+        let robots = picture
+            .chain(noise)
+            .map(|(x, y)| {
+                let v = (random(size.0) as isize - 50, random(size.1) as isize - 51);
+                let mut robot = Robot {
+                    pos: (x, y),
+                    v: (-v.0, -v.1),
+                };
+                robot.go(target, size);
+                robot.v = v;
+                robot
+            })
+            .collect::<Vec<_>>();
+
+        let (step, frame) = easter_egg(&robots, size);
+        assert_eq!(step, target);
+        assert!(frame.contains(&"#".repeat(20)));
+        assert!(stats(&robots, step, size).largest_cluster >= 400);
+        assert!(stats(&robots, step, size).quadrant_score < stats(&robots, 0, size).quadrant_score);
+    }
+
+    #[test]
+    fn same_as_brute_force() {
+        let size = (11, 13);
+        let target = 100;
+        // A 3x3 square and a few robots around, velocities are all different
+        let robots = (0..3)
+            .flat_map(|x| (0..3).map(move |y| (x + 4, y + 5)))
+            .chain([(0, 0), (10, 1), (2, 12), (8, 9)])
+            .enumerate()
+            .map(|(i, (x, y))| {
+                let v = (i as isize % 11, (i * 5 % 13) as isize);
+                let mut robot = Robot {
+                    pos: (x, y),
+                    v: (-v.0, -v.1),
+                };
+                robot.go(target, size);
+                robot.v = v;
+                robot
+            })
+            .collect::<Vec<_>>();
+
+        let brute = (0..size.0 * size.1)
+            .max_by_key(|step| {
+                let stats = stats(&robots, *step, size);
+                (stats.largest_cluster, Reverse(stats.quadrant_score))
+            })
+            .unwrap();
+        let (step, _) = easter_egg(&robots, size);
+        assert_eq!(brute, target);
+        assert_eq!(step, brute);
     }
 }
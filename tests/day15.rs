@@ -1,42 +1,22 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, HashSet};
+    use std::{
+        collections::{HashMap, HashSet},
+        io::Write,
+        path::Path,
+    };
 
-    use advent2024::*;
+    use advent2024::{image::Image, *};
 
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     enum Tile {
         Empty,
         Wall,
-        BoxLeft,
-        BoxRight,
+        // Index of the box in `Warehouse::boxes`
+        Box(usize),
         Robot,
     }
 
-    impl TryFrom<u8> for Tile {
-        type Error = ();
-
-        fn try_from(value: u8) -> Result<Self, Self::Error> {
-            match value {
-                b'.' => Ok(Tile::Empty),
-                b'#' => Ok(Tile::Wall),
-                b'O' => Ok(Tile::BoxLeft),
-                b'@' => Ok(Tile::Robot),
-                _ => Err(()),
-            }
-        }
-    }
-
-    impl Tile {
-        pub fn double(&self) -> [Self; 2] {
-            match self {
-                Self::Robot => [Self::Robot, Self::Empty],
-                Self::BoxLeft => [Self::BoxLeft, Self::BoxRight],
-                other => [*other, *other],
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Copy)]
     enum Direction {
         Left,
@@ -68,6 +48,39 @@ mod tests {
                 Self::Down => (1, 0),
             }
         }
+
+        fn shift(&self, (y, x): (usize, usize), back: bool) -> (usize, usize) {
+            let (oy, ox) = self.offset();
+            let sign = if back { -1 } else { 1 };
+            (
+                (y as isize + oy * sign) as usize,
+                (x as isize + ox * sign) as usize,
+            )
+        }
+    }
+
+    // A box of any shape, `cells` is the occupied footprint
+    #[derive(Debug, Clone)]
+    struct Crate {
+        cells: Vec<(usize, usize)>,
+    }
+
+    impl Crate {
+        fn gps(&self) -> usize {
+            let row = self.cells.iter().map(|(y, _)| *y).min().unwrap();
+            let col = self.cells.iter().map(|(_, x)| *x).min().unwrap();
+            row * 100 + col
+        }
+
+        fn fill(&self, map: &mut [Vec<Tile>], tile: Tile) {
+            self.cells.iter().for_each(|(y, x)| map[*y][*x] = tile);
+        }
+
+        fn shift(&mut self, dir: Direction, back: bool) {
+            self.cells
+                .iter_mut()
+                .for_each(|pos| *pos = dir.shift(*pos, back));
+        }
     }
 
     #[derive(Debug)]
@@ -76,85 +89,82 @@ mod tests {
     }
 
     impl Robot {
-        fn go(&mut self, map: &mut [Vec<Tile>], dir: Direction) -> Option<usize> {
-            if let Some(boxes) = self.moved_boxes(map, dir) {
-                let (oy, ox) = dir.offset();
-                boxes.keys().copied().for_each(|(y, x)| {
-                    map[y][x] = Tile::Empty;
-                });
-                boxes.iter().for_each(|((y, x), tile)| {
-                    map[(*y as isize + oy) as usize][(*x as isize + ox) as usize] = *tile;
-                });
-
-                map[self.position.0][self.position.1] = Tile::Empty;
-                self.position = (
-                    (self.position.0 as isize + oy) as usize,
-                    (self.position.1 as isize + ox) as usize,
-                );
-                map[self.position.0][self.position.1] = Tile::Robot;
-
-                Some(boxes.len())
-            } else {
-                None
-            }
+        fn go(
+            &mut self,
+            map: &mut [Vec<Tile>],
+            boxes: &mut [Crate],
+            dir: Direction,
+        ) -> Option<usize> {
+            let moved = self.moved_boxes(map, boxes, dir)?;
+            self.apply(map, boxes, dir, &moved);
+            Some(moved.len())
         }
 
+        fn apply(
+            &mut self,
+            map: &mut [Vec<Tile>],
+            boxes: &mut [Crate],
+            dir: Direction,
+            moved: &[usize],
+        ) {
+            moved
+                .iter()
+                .for_each(|id| boxes[*id].fill(map, Tile::Empty));
+            moved.iter().for_each(|id| {
+                boxes[*id].shift(dir, false);
+                boxes[*id].fill(map, Tile::Box(*id));
+            });
+
+            map[self.position.0][self.position.1] = Tile::Empty;
+            self.position = dir.shift(self.position, false);
+            map[self.position.0][self.position.1] = Tile::Robot;
+        }
+
+        // Revert `apply`
+        fn undo(
+            &mut self,
+            map: &mut [Vec<Tile>],
+            boxes: &mut [Crate],
+            dir: Direction,
+            moved: &[usize],
+        ) {
+            map[self.position.0][self.position.1] = Tile::Empty;
+            moved
+                .iter()
+                .for_each(|id| boxes[*id].fill(map, Tile::Empty));
+            moved.iter().for_each(|id| {
+                boxes[*id].shift(dir, true);
+                boxes[*id].fill(map, Tile::Box(*id));
+            });
+
+            self.position = dir.shift(self.position, true);
+            map[self.position.0][self.position.1] = Tile::Robot;
+        }
+
+        // Follow footprints of boxes in the way, returns the ids of all boxes to push
         fn moved_boxes(
             &self,
             map: &[Vec<Tile>],
+            boxes: &[Crate],
             dir: Direction,
-        ) -> Option<HashMap<(usize, usize), Tile>> {
-            let (oy, ox) = dir.offset();
-            let mut boxes: HashMap<(usize, usize), Tile> = HashMap::new();
-            let mut boxes_count = 0;
-            let mut checks = HashSet::new();
-            checks.insert(self.position);
-            loop {
-                checks = checks
-                    .into_iter()
-                    .map(|(y, x)| ((y as isize + oy) as usize, (x as isize + ox) as usize))
-                    .collect();
-
-                if checks
-                    .iter()
-                    .any(|(y, x)| matches!(map[*y][*x], Tile::Wall))
-                {
-                    return None;
-                }
+        ) -> Option<Vec<usize>> {
+            let mut moved = vec![];
+            let mut checked = HashSet::new();
+            let mut checks = vec![self.position];
 
-                checks = checks
-                    .iter()
-                    .copied()
-                    .flat_map(|(y, x)| match map[y][x] {
-                        Tile::BoxLeft => {
-                            boxes.insert((y, x), Tile::BoxLeft);
-                            if matches!(map[y][x + 1], Tile::BoxRight) {
-                                boxes.insert((y, x + 1), Tile::BoxRight);
-                                [Some((y, x)), Some((y, x + 1))]
-                            } else {
-                                [Some((y, x)), None]
-                            }
-                        }
-                        Tile::BoxRight => {
-                            boxes.insert((y, x), Tile::BoxRight);
-                            if matches!(map[y][x - 1], Tile::BoxLeft) {
-                                boxes.insert((y, x - 1), Tile::BoxLeft);
-                                [Some((y, x)), Some((y, x - 1))]
-                            } else {
-                                [Some((y, x)), None]
-                            }
-                        }
-                        _ => [None, None],
-                    })
-                    .flatten()
-                    .collect();
-
-                if boxes.len() == boxes_count {
-                    return Some(boxes);
+            while let Some(pos) = checks.pop() {
+                let (y, x) = dir.shift(pos, false);
+                match map[y][x] {
+                    Tile::Wall => return None,
+                    Tile::Box(id) if checked.insert(id) => {
+                        moved.push(id);
+                        checks.extend(boxes[id].cells.iter().copied());
+                    }
+                    _ => {}
                 }
-
-                boxes_count = boxes.len();
             }
+
+            Some(moved)
         }
     }
 
@@ -162,74 +172,223 @@ mod tests {
     struct Warehouse {
         robot: Robot,
         map: Vec<Vec<Tile>>,
+        boxes: Vec<Crate>,
     }
 
     impl Warehouse {
         fn robot_move(&mut self, dir: Direction) -> Option<usize> {
-            self.robot.go(&mut self.map, dir)
+            self.robot.go(&mut self.map, &mut self.boxes, dir)
         }
 
         fn gps(&self) -> usize {
-            self.map
-                .iter()
-                .enumerate()
-                .flat_map(|(row, line)| {
-                    line.iter()
-                        .enumerate()
-                        .map(move |(col, tile)| (row, col, *tile))
-                })
-                .filter(|(_, _, tile)| matches!(tile, Tile::BoxLeft))
-                .map(|(row, col, _)| row * 100 + col)
-                .sum()
+            self.boxes.iter().map(Crate::gps).sum()
+        }
+
+        fn ascii(&self) -> String {
+            let mut result = String::new();
+            for (row, line) in self.map.iter().enumerate() {
+                for (col, tile) in line.iter().enumerate() {
+                    result.push(match tile {
+                        Tile::Empty => '.',
+                        Tile::Wall => '#',
+                        Tile::Robot => '@',
+                        Tile::Box(id) => match &self.boxes[*id].cells[..] {
+                            [_] => 'O',
+                            [l, r] if l.0 == r.0 && l.1 + 1 == r.1 => {
+                                if *l == (row, col) {
+                                    '['
+                                } else {
+                                    ']'
+                                }
+                            }
+                            _ => (b'a' + (*id % 26) as u8) as char,
+                        },
+                    });
+                }
+                result.push('\n');
+            }
+            result
+        }
+
+        fn write_ppm(&self, writer: impl Write) -> std::io::Result<()> {
+            Image::from_grid(&self.map, |tile| match tile {
+                Tile::Empty => (0, 0, 0),
+                Tile::Wall => (128, 128, 128),
+                Tile::Box(_) => (200, 140, 60),
+                Tile::Robot => (255, 0, 0),
+            })
+            .write(writer)
         }
     }
 
-    fn data(path: &str, double: bool) -> (Warehouse, impl Iterator<Item = Direction>) {
-        let mut lines = read_by_line(path);
+    #[derive(Debug, Clone, Copy)]
+    enum FrameFormat {
+        Ascii,
+        Ppm,
+    }
+
+    // Records every move so the warehouse can go back and forth in time
+    struct Replay {
+        warehouse: Warehouse,
+        moves: Vec<Direction>,
+        // Ids of boxes displaced by each applied move, `None` if the move is blocked
+        history: Vec<Option<Vec<usize>>>,
+    }
 
-        let m = lines.by_ref().take_while(|x| !x.trim_end().is_empty());
+    impl Replay {
+        fn new(warehouse: Warehouse, moves: impl IntoIterator<Item = Direction>) -> Self {
+            Self {
+                warehouse,
+                moves: moves.into_iter().collect(),
+                history: vec![],
+            }
+        }
 
+        // Count of applied moves
+        fn current(&self) -> usize {
+            self.history.len()
+        }
+
+        fn forward(&mut self) -> bool {
+            let Some(dir) = self.moves.get(self.current()).copied() else {
+                return false;
+            };
+            let Warehouse { robot, map, boxes } = &mut self.warehouse;
+            let moved = robot.moved_boxes(map, boxes, dir);
+            if let Some(moved) = &moved {
+                robot.apply(map, boxes, dir, moved);
+            }
+            self.history.push(moved);
+            true
+        }
+
+        fn backward(&mut self) -> bool {
+            let Some(moved) = self.history.pop() else {
+                return false;
+            };
+            let dir = self.moves[self.current()];
+            if let Some(moved) = moved {
+                let Warehouse { robot, map, boxes } = &mut self.warehouse;
+                robot.undo(map, boxes, dir, &moved);
+            }
+            true
+        }
+
+        fn jump(&mut self, n: usize) {
+            let n = n.min(self.moves.len());
+            while self.current() < n {
+                self.forward();
+            }
+            while self.current() > n {
+                self.backward();
+            }
+        }
+
+        fn write_frame(&self, mut writer: impl Write, format: FrameFormat) -> std::io::Result<()> {
+            match format {
+                FrameFormat::Ascii => writer.write_all(self.warehouse.ascii().as_bytes()),
+                FrameFormat::Ppm => self.warehouse.write_ppm(writer),
+            }
+        }
+
+        // Write frames of move `start` to `end`(inclusive) as numbered files into `dir`
+        fn export(
+            &mut self,
+            dir: impl AsRef<Path>,
+            format: FrameFormat,
+            start: usize,
+            end: usize,
+        ) -> std::io::Result<()> {
+            std::fs::create_dir_all(dir.as_ref())?;
+            let ext = match format {
+                FrameFormat::Ascii => "txt",
+                FrameFormat::Ppm => "ppm",
+            };
+            for n in start..=end.min(self.moves.len()) {
+                self.jump(n);
+                let f = std::fs::File::create(dir.as_ref().join(format!("{:05}.{}", n, ext)))?;
+                let mut writer = std::io::BufWriter::new(f);
+                self.write_frame(&mut writer, format)?;
+                writer.flush()?;
+            }
+            Ok(())
+        }
+    }
+
+    // Every tile is scaled to `scale` tiles wide. `O` is a box, `[]` is a box with its two
+    // halves, and cells with the same letter form one box of any shape.
+    fn parse(
+        mut lines: impl Iterator<Item = String>,
+        scale: usize,
+    ) -> Option<(Warehouse, impl Iterator<Item = Direction>)> {
         let mut robot = (0, 0);
-        let map: Vec<Vec<_>> = m
+        let mut boxes: Vec<Crate> = vec![];
+        let mut letters: HashMap<u8, usize> = HashMap::new();
+
+        let map: Vec<Vec<_>> = lines
+            .by_ref()
+            .take_while(|x| !x.trim_end().is_empty())
             .enumerate()
             .map(|(row, line)| {
-                let tiles = line
-                    .into_bytes()
-                    .into_iter()
-                    .filter_map(|tile| tile.try_into().ok())
-                    .enumerate()
-                    .map(|(col, tile)| {
-                        if matches!(tile, Tile::Robot) {
-                            robot = (row, col);
+                let mut tiles = vec![];
+                // A stray `\r` is skipped, any other unknown byte is an error
+                let bytes = line.trim_end().bytes().filter(|b| *b != b'\r');
+                let mut prev = None;
+                for (col, b) in bytes.enumerate() {
+                    let id = match b {
+                        b'#' | b'.' | b'@' => None,
+                        b'O' | b'[' => {
+                            boxes.push(Crate { cells: vec![] });
+                            Some(boxes.len() - 1)
                         }
-                        tile
-                    });
-
-                if double {
-                    tiles.flat_map(|tile| tile.double()).collect()
-                } else {
-                    tiles.collect()
+                        b']' if prev == Some(b'[') => Some(boxes.len() - 1),
+                        b'a'..=b'z' => Some(*letters.entry(b).or_insert_with(|| {
+                            boxes.push(Crate { cells: vec![] });
+                            boxes.len() - 1
+                        })),
+                        _ => return None,
+                    };
+                    prev = Some(b);
+                    for k in 0..scale {
+                        let pos = (row, col * scale + k);
+                        tiles.push(match (b, id) {
+                            (b'#', _) => Tile::Wall,
+                            (b'@', _) if k == 0 => {
+                                robot = pos;
+                                Tile::Robot
+                            }
+                            (_, Some(id)) => {
+                                boxes[id].cells.push(pos);
+                                Tile::Box(id)
+                            }
+                            _ => Tile::Empty,
+                        });
+                    }
                 }
+                Some(tiles)
             })
-            .collect();
+            .collect::<Option<_>>()?;
 
         let warehouse = Warehouse {
-            robot: Robot {
-                position: (robot.0, robot.1 * if double { 2 } else { 1 }),
-            },
+            robot: Robot { position: robot },
             map,
+            boxes,
         };
 
         let movements = lines
             .flat_map(|line| line.into_bytes())
             .filter_map(|b| b.try_into().ok());
 
-        (warehouse, movements)
+        Some((warehouse, movements))
+    }
+
+    fn data(path: &str, scale: usize) -> (Warehouse, impl Iterator<Item = Direction>) {
+        parse(read_by_line(path), scale).unwrap()
     }
 
     #[test]
     fn part1() {
-        let (mut warehouse, movement) = data("tests/data/day15.input.txt", false);
+        let (mut warehouse, movement) = data("tests/data/day15.input.txt", 1);
         movement.for_each(|dir| {
             warehouse.robot_move(dir);
         });
@@ -239,10 +398,201 @@ mod tests {
 
     #[test]
     fn part2() {
-        let (mut warehouse, movement) = data("tests/data/day15.input.txt", true);
+        let (mut warehouse, movement) = data("tests/data/day15.input.txt", 2);
         movement.for_each(|dir| {
             warehouse.robot_move(dir);
         });
         assert_eq!(warehouse.gps(), 1512860);
     }
+
+    const EXAMPLE: &str = "##########
+#..O..O.O#
+#......O.#
+#.OO..O.O#
+#..O@..O.#
+#O#..O...#
+#O..O..O.#
+#.OO.O.OO#
+#....O...#
+##########
+
+<vv>^<v^>v>^vv^v>v<>v^v<v<^vv<<<^><<><>>v<vvv<>^v^>^<<<><<v<<<v^vv^v>^
+vvv<<^>^v^^><<>>><>^<<><^vv^^<>vvv<>><^^v>^>vv<>v<<<<v<^v>^<^^>>>^<v<v
+><>vv>v^v^<>><>>>><^^>vv>v<^^^>>v^v^<^^>v^^>v^<^v>v<>>v^v^<v>v^^<^^vv<
+<<v<^>>^^^^>>>v^<>vvv^><v<<<>^^^vv^<vvv>^>v<^^^^v<>^>vvvv><>>v^<<^^^^^
+^><^><>>><>^^<<^^v>>><^<v>^<vv>>v>>>^v><>^v><<<<v>>v<v<v>vvv>^<><<>^><
+^>><>^v<><^vvv<^^<><v<<<<<><^v<<<><<<^^<v<^^^><^>>^<v^><<<^>>^v<v^v<v^
+>^>>^v>vv>^<<^v<>><<><<v<<v><>v<^vv<<<>^^v^>^^>>><<^v>>v^v><^^>>^<>vv^
+<><^^>^^^<><vvvvv^v<v<<>^v<v>v<<^><<><<><<<^^<<<^<<>><<><^^^>^^<>^>v<>
+^^>vv<^v^v<vv>^<><v<^v>^^^>>>^^vvv^>vvv<>>>^<^>>>>>^<<^v>^vvv<>^<><<v>
+v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^
+";
+
+    const SMALL_WIDE: &str = "#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######
+
+<vv<<^^<<^^
+";
+
+    fn example(s: &str, scale: usize) -> (Warehouse, Vec<Direction>) {
+        let (warehouse, moves) = parse(s.lines().map(str::to_string), scale).unwrap();
+        (warehouse, moves.collect())
+    }
+
+    #[test]
+    fn example_replay() {
+        for (scale, gps) in [(1, 10092), (2, 9021)] {
+            let (warehouse, moves) = example(EXAMPLE, scale);
+            let mut replay = Replay::new(warehouse, moves);
+
+            let mut frames = vec![replay.warehouse.ascii()];
+            while replay.forward() {
+                frames.push(replay.warehouse.ascii());
+            }
+            assert_eq!(replay.current(), 700);
+            assert_eq!(replay.warehouse.gps(), gps);
+
+            while replay.backward() {
+                assert_eq!(replay.warehouse.ascii(), frames[replay.current()]);
+            }
+            assert_eq!(replay.current(), 0);
+
+            for n in [350, 12, 699, 700, 0, 10000] {
+                replay.jump(n);
+                assert_eq!(replay.warehouse.ascii(), frames[n.min(700)]);
+            }
+        }
+    }
+
+    #[test]
+    fn example_frames() {
+        let (warehouse, moves) = example(SMALL_WIDE, 2);
+        let mut replay = Replay::new(warehouse, moves);
+        replay.jump(usize::MAX);
+        assert_eq!(
+            replay.warehouse.ascii(),
+            "##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+"
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("advent2024-day15-frames-{}", std::process::id()));
+        replay.export(&dir, FrameFormat::Ascii, 0, 100).unwrap();
+        replay.export(&dir, FrameFormat::Ppm, 3, 3).unwrap();
+
+        let first = std::fs::read_to_string(dir.join("00000.txt")).unwrap();
+        assert!(first.contains("##....[][]@.##"));
+        assert!(dir.join("00011.txt").exists() && !dir.join("00012.txt").exists());
+
+        let ppm = std::fs::read(dir.join("00003.ppm")).unwrap();
+        let header = b"P6\n14 7\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 14 * 7 * 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn consistent(warehouse: &Warehouse) -> bool {
+        let cells = warehouse
+            .map
+            .iter()
+            .flatten()
+            .filter(|tile| matches!(tile, Tile::Box(_)))
+            .count();
+        cells == warehouse.boxes.iter().map(|b| b.cells.len()).sum()
+            && warehouse.boxes.iter().enumerate().all(|(id, b)| {
+                b.cells
+                    .iter()
+                    .all(|(y, x)| warehouse.map[*y][*x] == Tile::Box(id))
+            })
+    }
+
+    #[test]
+    fn example_scale() {
+        for scale in [1, 2, 3, 5] {
+            let (warehouse, moves) = example(EXAMPLE, scale);
+            let mut replay = Replay::new(warehouse, moves);
+            let before = replay.warehouse.ascii();
+            while replay.forward() {
+                assert!(consistent(&replay.warehouse));
+            }
+            assert!(replay
+                .warehouse
+                .boxes
+                .iter()
+                .all(|b| b.cells.len() == scale));
+            replay.jump(0);
+            assert_eq!(replay.warehouse.ascii(), before);
+        }
+
+        // `\r` of Windows line endings is skipped
+        let crlf = EXAMPLE.replace('\n', "\r\n");
+        let (warehouse, moves) = parse(crlf.split('\n').map(str::to_string), 2).unwrap();
+        let mut replay = Replay::new(warehouse, moves.collect::<Vec<_>>());
+        replay.jump(usize::MAX);
+        assert_eq!(replay.warehouse.gps(), 9021);
+
+        // Malformed maps
+        let bad = |s: &str| parse(s.lines().map(str::to_string), 1).is_none();
+        assert!(bad("#####\n#]..#\n#####"));
+        assert!(bad("#####\n#.].#\n#####"));
+        assert!(bad("#####\n#.@?#\n#####"));
+        assert!(!bad("#####\n#[]@#\n#####"));
+    }
+
+    #[test]
+    fn shaped_boxes() {
+        let (mut warehouse, _) = example(
+            "#######
+#.....#
+#.aa..#
+#..a@.#
+#######
+",
+            1,
+        );
+        assert_eq!(warehouse.robot_move(Direction::Left), Some(1));
+        assert_eq!(warehouse.boxes[0].cells, [(2, 1), (2, 2), (3, 2)]);
+        assert_eq!(warehouse.robot_move(Direction::Left), None);
+        assert!(consistent(&warehouse));
+
+        // Tall box pushes the one above it, a single column of it is enough to be blocked
+        let (mut warehouse, _) = example(
+            "########
+#......#
+#..O...#
+#..b...#
+#..bb..#
+#...@..#
+########
+",
+            1,
+        );
+        assert_eq!(warehouse.gps(), 203 + 303);
+        assert_eq!(warehouse.robot_move(Direction::Up), Some(2));
+        assert_eq!(warehouse.gps(), 103 + 203);
+        assert_eq!(warehouse.robot_move(Direction::Up), None);
+        assert!(consistent(&warehouse));
+        assert_eq!(
+            warehouse.ascii(),
+            "########
+#..O...#
+#..b...#
+#..bb..#
+#...@..#
+#......#
+########
+"
+        );
+    }
 }
@@ -1,8 +1,15 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, HashMap, HashSet},
+        io::Write,
+    };
 
-    use advent2024::*;
+    use advent2024::{
+        image::{Image, Rgb},
+        *,
+    };
 
     #[derive(Debug, Clone, Copy)]
     enum Tile {
@@ -10,7 +17,7 @@ mod tests {
         Wall,
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
     enum Direction {
         Left,
         Right,
@@ -51,6 +58,7 @@ mod tests {
                 Action::Go => *self,
                 Action::Clockwise => self.cc(),
                 Action::CounterClockwise => self.c(),
+                Action::UTurn => self.invert(),
             }
         }
 
@@ -69,52 +77,100 @@ mod tests {
         Go,
         Clockwise,
         CounterClockwise,
+        UTurn,
     }
 
-    struct MazePath(Vec<((usize, usize), Direction)>);
+    type Score = usize;
+    type Pos = (usize, usize);
 
-    impl MazePath {
-        pub fn score(&self) -> usize {
-            let mut result = 0;
-            self.0.iter().map(|(_, dir)| dir).reduce(|dir, d2| {
-                if dir == d2 {
-                    result += 1;
-                } else {
-                    result += 1000;
-                }
-                d2
-            });
+    #[derive(Debug, Clone)]
+    struct CostModel {
+        step: Score,
+        turn: Score,
+        // `None` means u-turn is done by two turns
+        u_turn: Option<Score>,
+        // Extra cost when stepping into a tile
+        tiles: HashMap<Pos, Score>,
+    }
 
-            result
+    impl Default for CostModel {
+        fn default() -> Self {
+            Self {
+                step: 1,
+                turn: 1000,
+                u_turn: None,
+                tiles: HashMap::new(),
+            }
         }
     }
 
-    type Score = usize;
-    type Pos = (usize, usize);
+    impl CostModel {
+        fn step_into(&self, pos: Pos) -> Score {
+            self.step + self.tiles.get(&pos).copied().unwrap_or_default()
+        }
+    }
+
+    struct MazePath(Vec<((usize, usize), Direction)>);
+
+    impl MazePath {
+        pub fn score(&self, cost: &CostModel) -> usize {
+            self.0
+                .windows(2)
+                .map(|w| {
+                    let [(p1, d1), (p2, d2)] = [w[0], w[1]];
+                    if p1 != p2 {
+                        cost.step_into(p2)
+                    } else if d1.invert() == d2 {
+                        cost.u_turn.unwrap_or(cost.turn * 2)
+                    } else {
+                        cost.turn
+                    }
+                })
+                .sum()
+        }
+    }
 
     #[derive(Debug)]
     struct MazeSolver<'a> {
         maze: &'a Maze,
+        cost: CostModel,
+        start_dir: Direction,
+        end_dir: Option<Direction>,
         record: HashMap<Pos, HashMap<Direction, (Score, HashSet<Action>)>>,
-        queue: VecDeque<(Pos, Direction, Score)>,
+        queue: BinaryHeap<Reverse<(Score, Pos, Direction)>>,
     }
 
     impl<'a> MazeSolver<'a> {
         pub fn new(maze: &'a Maze) -> Self {
             let record = HashMap::new();
-            let queue = VecDeque::new();
+            let queue = BinaryHeap::new();
             Self {
                 maze,
+                cost: CostModel::default(),
+                start_dir: Direction::Right,
+                end_dir: None,
                 record,
                 queue,
             }
         }
 
+        pub fn with_cost(mut self, cost: CostModel) -> Self {
+            self.cost = cost;
+            self
+        }
+
+        // `None` end heading means reach the end facing any direction
+        pub fn with_headings(mut self, start: Direction, end: Option<Direction>) -> Self {
+            self.start_dir = start;
+            self.end_dir = end;
+            self
+        }
+
         fn enqueue(&mut self, action: Action, pos: (usize, usize), dir: Direction, score: usize) {
             let entry = self.record.entry(pos).or_default();
             let record = entry.get_mut(&dir);
             if record.as_ref().is_none_or(|(last, _)| *last > score) {
-                self.queue.push_back((pos, dir, score));
+                self.queue.push(Reverse((score, pos, dir)));
                 entry.insert(dir, (score, Some(action).into_iter().collect()));
             } else if record.as_ref().is_some_and(|(last, _)| *last == score) {
                 record.unwrap().1.insert(action);
@@ -122,25 +178,53 @@ mod tests {
         }
 
         fn scan(&mut self) -> Option<Vec<(Direction, Score)>> {
-            self.enqueue(Action::Go, self.maze.start, Direction::Right, 0);
+            let (start, start_dir) = (self.maze.start, self.start_dir);
+            self.record
+                .entry(start)
+                .or_default()
+                .insert(start_dir, (0, HashSet::new()));
+            self.queue.push(Reverse((0, start, start_dir)));
+
+            while let Some(Reverse((score, pos, dir))) = self.queue.pop() {
+                if self.record[&pos][&dir].0 < score {
+                    continue;
+                }
 
-            while let Some((pos, dir, score)) = self.queue.pop_front() {
                 let new_pos = Maze::move_dir(pos, dir);
                 if matches!(self.maze.get(new_pos), Tile::Empty) {
-                    self.enqueue(Action::Go, new_pos, dir, score + 1);
+                    let step = self.cost.step_into(new_pos);
+                    self.enqueue(Action::Go, new_pos, dir, score + step);
+                }
+                let (turn, u_turn) = (self.cost.turn, self.cost.u_turn);
+                self.enqueue(Action::Clockwise, pos, dir.c(), score + turn);
+                self.enqueue(Action::CounterClockwise, pos, dir.cc(), score + turn);
+                if let Some(u_turn) = u_turn {
+                    self.enqueue(Action::UTurn, pos, dir.invert(), score + u_turn);
                 }
-                self.enqueue(Action::Clockwise, pos, dir.c(), score + 1000);
-                self.enqueue(Action::CounterClockwise, pos, dir.cc(), score + 1000);
             }
 
             let ends = self
                 .record
                 .get(&self.maze.end)?
                 .iter()
+                .filter(|(dir, _)| self.end_dir.is_none_or(|end| end == **dir))
                 .map(|(dir, (score, _))| (*dir, *score))
-                .collect();
+                .collect::<Vec<_>>();
 
-            Some(ends)
+            if ends.is_empty() {
+                None
+            } else {
+                Some(ends)
+            }
+        }
+
+        // Lowest score of reaching every tile, facing any direction
+        pub fn distances(mut self) -> HashMap<Pos, Score> {
+            self.scan();
+            self.record
+                .into_iter()
+                .filter_map(|(pos, dirs)| Some((pos, dirs.into_values().map(|(s, _)| s).min()?)))
+                .collect()
         }
 
         pub fn solve(mut self) -> Option<Vec<MazePath>> {
@@ -182,6 +266,10 @@ mod tests {
                 }
 
                 fn init(mut self, global: &mut Global) -> [Option<Self>; 2] {
+                    // Zero cost moves can make the records cyclic
+                    if global.path.contains(&(self.pos, self.dir)) {
+                        return [None, None];
+                    }
                     if let Some((_, actions)) = global
                         .solver
                         .record
@@ -190,7 +278,7 @@ mod tests {
                     {
                         global.path.push((self.pos, self.dir));
                         if self.pos == global.solver.maze.start
-                            && matches!(self.dir, Direction::Right)
+                            && self.dir == global.solver.start_dir
                         {
                             global.paths.push({
                                 let mut p = global.path.clone();
@@ -291,10 +379,140 @@ mod tests {
         }
     }
 
-    fn data(path: &str) -> Maze {
+    impl Direction {
+        fn arrow(&self) -> char {
+            match self {
+                Self::Left => '<',
+                Self::Right => '>',
+                Self::Up => '^',
+                Self::Down => 'v',
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Cell {
+        symbol: char,
+        color: Rgb,
+    }
+
+    const WALL: Cell = Cell {
+        symbol: '#',
+        color: (96, 96, 96),
+    };
+    const EMPTY: Cell = Cell {
+        symbol: '.',
+        color: (24, 24, 24),
+    };
+    const PATH_COLOR: Rgb = (64, 200, 64);
+    const TURN_COLOR: Rgb = (255, 160, 0);
+    const CROSS_COLOR: Rgb = (255, 255, 0);
+
+    struct MazeRender(Vec<Vec<Cell>>);
+
+    impl MazeRender {
+        fn base(maze: &Maze) -> Self {
+            Self(
+                maze.map
+                    .iter()
+                    .map(|line| {
+                        line.iter()
+                            .map(|tile| match tile {
+                                Tile::Wall => WALL,
+                                Tile::Empty => EMPTY,
+                            })
+                            .collect()
+                    })
+                    .collect(),
+            )
+        }
+
+        fn markers(mut self, maze: &Maze) -> Self {
+            let (start, end) = (maze.start, maze.end);
+            self.0[start.0][start.1] = Cell {
+                symbol: 'S',
+                color: (64, 128, 255),
+            };
+            self.0[end.0][end.1] = Cell {
+                symbol: 'E',
+                color: (255, 64, 64),
+            };
+            self
+        }
+
+        // Every tile of best paths, with the direction leaving it. Tiles where a path turns
+        // and tiles crossed by paths in different directions get their own color.
+        fn paths(maze: &Maze, paths: &[MazePath]) -> Self {
+            let mut tiles: HashMap<Pos, (HashSet<Direction>, bool)> = HashMap::new();
+            for path in paths {
+                for w in path.0.windows(2) {
+                    let [(p1, d1), (p2, _)] = [w[0], w[1]];
+                    let entry = tiles.entry(p1).or_default();
+                    if p1 == p2 {
+                        entry.1 = true;
+                    } else {
+                        entry.0.insert(d1);
+                    }
+                }
+            }
+
+            let mut render = Self::base(maze);
+            for ((row, col), (dirs, turned)) in tiles {
+                render.0[row][col] = if dirs.len() > 1 {
+                    Cell {
+                        symbol: '+',
+                        color: CROSS_COLOR,
+                    }
+                } else {
+                    Cell {
+                        symbol: dirs.iter().next().map_or('o', Direction::arrow),
+                        color: if turned { TURN_COLOR } else { PATH_COLOR },
+                    }
+                };
+            }
+            render.markers(maze)
+        }
+
+        // Blue for near the start, red for the farthest, black for unreachable
+        fn heatmap(maze: &Maze, distances: &HashMap<Pos, Score>) -> Self {
+            let max = distances.values().copied().max().unwrap_or(0).max(1);
+            let mut render = Self::base(maze);
+            for (&(row, col), &distance) in distances {
+                let hot = (distance * 255 / max) as u8;
+                render.0[row][col] = Cell {
+                    symbol: '█',
+                    color: (hot, 0, 255 - hot),
+                };
+            }
+            render.markers(maze)
+        }
+
+        fn ansi(&self) -> String {
+            let mut result = String::new();
+            for line in &self.0 {
+                let mut last = None;
+                for cell in line {
+                    if last != Some(cell.color) {
+                        let (r, g, b) = cell.color;
+                        result += &format!("\x1b[38;2;{};{};{}m", r, g, b);
+                        last = Some(cell.color);
+                    }
+                    result.push(cell.symbol);
+                }
+                result += "\x1b[0m\n";
+            }
+            result
+        }
+
+        fn write_ppm(&self, writer: impl Write) -> std::io::Result<()> {
+            Image::from_grid(&self.0, |cell| cell.color).write(writer)
+        }
+    }
+
+    fn parse(lines: impl Iterator<Item = String>) -> Maze {
         let mut start = (0, 0);
         let mut end = (0, 0);
-        let map: Vec<_> = read_by_line(path)
+        let map: Vec<_> = lines
             .enumerate()
             .map(|(row, line)| {
                 line.into_bytes()
@@ -321,12 +539,16 @@ mod tests {
         Maze { map, start, end }
     }
 
+    fn data(path: &str) -> Maze {
+        parse(read_by_line(path))
+    }
+
     #[test]
     fn part1_2() {
         let maze = data("tests/data/day16.input.txt");
         let paths = MazeSolver::new(&maze).solve().unwrap();
 
-        assert_eq!(paths[0].score(), 102460);
+        assert_eq!(paths[0].score(&CostModel::default()), 102460);
 
         let path_tiles_count = paths
             .into_iter()
@@ -336,4 +558,190 @@ mod tests {
 
         assert_eq!(path_tiles_count, 527);
     }
+
+    const EXAMPLE1: &str = "###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+
+    const EXAMPLE2: &str = "#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################";
+
+    fn example(s: &str) -> Maze {
+        parse(s.lines().map(str::to_string))
+    }
+
+    fn tiles(paths: &[MazePath]) -> usize {
+        paths
+            .iter()
+            .flat_map(|p| p.0.iter().map(|(pos, _)| *pos))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    #[test]
+    fn example_default_cost() {
+        for (input, score, count) in [(EXAMPLE1, 7036, 45), (EXAMPLE2, 11048, 64)] {
+            let maze = example(input);
+            let paths = MazeSolver::new(&maze).solve().unwrap();
+            assert!(paths
+                .iter()
+                .all(|p| p.score(&CostModel::default()) == score));
+            assert_eq!(tiles(&paths), count);
+        }
+    }
+
+    #[test]
+    fn example_custom_cost() {
+        let maze = example(EXAMPLE1);
+
+        // Without turn cost, it is the plain shortest path
+        let free_turn = CostModel {
+            turn: 0,
+            ..Default::default()
+        };
+        let paths = MazeSolver::new(&maze)
+            .with_cost(free_turn.clone())
+            .solve()
+            .unwrap();
+        let mut distance = HashMap::from([(maze.start, 0)]);
+        let mut q = std::collections::VecDeque::from([maze.start]);
+        while let Some(pos) = q.pop_front() {
+            for next in neighbors(pos).into_iter().flatten() {
+                if matches!(maze.get(next), Tile::Empty) && !distance.contains_key(&next) {
+                    distance.insert(next, distance[&pos] + 1);
+                    q.push_back(next);
+                }
+            }
+        }
+        assert!(paths
+            .iter()
+            .all(|p| p.score(&free_turn) == distance[&maze.end]));
+
+        // Heavy tiles on the best paths push the solver to other routes
+        let mut heavy = CostModel::default();
+        let best = MazeSolver::new(&maze).solve().unwrap();
+        for (pos, _) in best.iter().flat_map(|p| p.0.iter()) {
+            if *pos != maze.start && *pos != maze.end {
+                heavy.tiles.insert(*pos, 10000);
+            }
+        }
+        let paths = MazeSolver::new(&maze)
+            .with_cost(heavy.clone())
+            .solve()
+            .unwrap();
+        let score = paths[0].score(&heavy);
+        assert!(paths.iter().all(|p| p.score(&heavy) == score));
+        assert!(best.iter().all(|p| p.score(&heavy) > score));
+    }
+
+    #[test]
+    fn example_headings() {
+        let maze = example(EXAMPLE2);
+        let cost = CostModel::default();
+
+        for start in [Direction::Up, Direction::Left, Direction::Down] {
+            for end in [Direction::Up, Direction::Right] {
+                let paths = MazeSolver::new(&maze)
+                    .with_headings(start, Some(end))
+                    .solve()
+                    .unwrap();
+                let score = paths[0].score(&cost);
+                assert!(paths.iter().all(|p| p.0[0] == (maze.start, start)
+                    && *p.0.last().unwrap() == (maze.end, end)
+                    && p.score(&cost) == score));
+            }
+        }
+
+        // Facing up at start saves the first turn, arriving facing right cost one more
+        let up = MazeSolver::new(&maze)
+            .with_headings(Direction::Up, None)
+            .solve()
+            .unwrap();
+        assert_eq!(up[0].score(&cost), 10048);
+        let up_right = MazeSolver::new(&maze)
+            .with_headings(Direction::Up, Some(Direction::Right))
+            .solve()
+            .unwrap();
+        assert_eq!(up_right[0].score(&cost), 11048);
+    }
+
+    fn strip_ansi(s: &str) -> String {
+        let mut result = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn example_render_paths() {
+        let maze = example(EXAMPLE1);
+        let paths = MazeSolver::new(&maze).solve().unwrap();
+        let render = MazeRender::paths(&maze, &paths);
+
+        let text = strip_ansi(&render.ansi());
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[13].as_bytes()[1], b'S');
+        assert_eq!(lines[1].as_bytes()[13], b'E');
+        let marked = text.chars().filter(|c| "<>^v+SE".contains(*c)).count();
+        assert_eq!(marked, tiles(&paths));
+        // The bottom-left corner of the best path turns from up to right
+        assert_eq!(lines[7].as_bytes()[3], b'>');
+        assert_eq!(render.0[7][3].color, TURN_COLOR);
+
+        let mut ppm = vec![];
+        render.write_ppm(&mut ppm).unwrap();
+        let header = b"P6\n15 15\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 15 * 15 * 3);
+        assert_eq!(&ppm[header.len()..header.len() + 3], [96, 96, 96]);
+    }
+
+    #[test]
+    fn example_render_heatmap() {
+        let maze = example(EXAMPLE2);
+        let distances = MazeSolver::new(&maze).distances();
+        assert_eq!(distances[&maze.start], 0);
+        assert_eq!(distances[&maze.end], 11048);
+
+        let render = MazeRender::heatmap(&maze, &distances);
+        let farthest = distances.iter().max_by_key(|(_, d)| **d).unwrap().0;
+        assert_eq!(render.0[farthest.0][farthest.1].color, (255, 0, 0));
+        // Right above the start, after the first turn
+        assert_eq!(distances[&(14, 1)], 1001);
+        assert_eq!(render.0[14][1].color, (21, 0, 234));
+        assert!(render.ansi().contains("\x1b[38;2;96;96;96m#"));
+    }
 }
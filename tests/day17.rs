@@ -1,10 +1,11 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, VecDeque};
+    use std::collections::VecDeque;
 
     use advent2024::*;
 
     #[derive(Debug, Clone)]
+    #[allow(clippy::upper_case_acronyms)]
     struct CPU {
         program: Vec<u8>,
         a: usize,
@@ -232,12 +233,8 @@ mod tests {
     fn part2() {
         let cpu = data("tests/data/day17.input.txt");
 
-        let map = (0..8)
-            .flat_map(|a| (0..8).map(move |b| ((a, b), a ^ b ^ 6)))
-            .fold(HashMap::<_, Vec<_>>::new(), |mut map, (input, output)| {
-                map.entry(output).or_default().push(input);
-                map
-            });
+        // output -> all (B, H) which produce it
+        let map = group((0..8).flat_map(|a| (0..8).map(move |b| (a ^ b ^ 6, (a, b)))));
 
         let target = cpu.program;
         let mut answers = Vec::new();
@@ -6,7 +6,11 @@ mod tests {
         fmt::Write,
     };
 
-    use advent2024::*;
+    use advent2024::{
+        dsu::DisjointSet,
+        image::{Image, Rgb},
+        *,
+    };
 
     #[derive(Debug, Clone, Copy)]
     enum Tile {
@@ -40,6 +44,7 @@ mod tests {
             (0, 0)
         }
 
+        #[allow(clippy::manual_saturating_arithmetic)]
         fn end(&self) -> (usize, usize) {
             let (y, x) = self.size;
             (
@@ -105,11 +110,80 @@ mod tests {
 
             Some(path)
         }
+
+        fn is_empty(&self, (y, x): (usize, usize)) -> bool {
+            matches!(self.tiles[y][x], Tile::Empty)
+        }
+
+        // Index of the first byte that cuts start from end. Drop all bytes, then take
+        // them away in reverse order and join the freed tile with its neighbors.
+        fn first_cut(&self, falls: &[(usize, usize)]) -> Option<usize> {
+            let mut m = self.clone();
+            falls.iter().for_each(|pos| m.byte_fall_at(*pos));
+
+            let (height, width) = self.size;
+            let idx = |(y, x): (usize, usize)| y * width + x;
+            let mut set = DisjointSet::new(height * width);
+            let join = |m: &Memory, set: &mut DisjointSet, pos| {
+                neighbors_limited(pos, m.size)
+                    .into_iter()
+                    .flatten()
+                    .filter(|n| m.is_empty(*n))
+                    .for_each(|n| {
+                        set.union(idx(pos), idx(n));
+                    });
+            };
+
+            for y in 0..height {
+                for x in 0..width {
+                    if m.is_empty((y, x)) {
+                        join(&m, &mut set, (y, x));
+                    }
+                }
+            }
+
+            let connected = |m: &Memory, set: &mut DisjointSet| {
+                m.is_empty(m.start()) && set.same(idx(m.start()), idx(m.end()))
+            };
+            if connected(&m, &mut set) {
+                return None;
+            }
+
+            // A tile falling again is freed only when its first fall is taken away
+            let mut first = HashMap::new();
+            for (i, pos) in falls.iter().enumerate() {
+                first.entry(*pos).or_insert(i);
+            }
+            for (i, &(y, x)) in falls.iter().enumerate().rev() {
+                if first[&(y, x)] != i || !self.is_empty((y, x)) {
+                    continue;
+                }
+                m.tiles[y][x] = Tile::Empty;
+                join(&m, &mut set, (y, x));
+                if connected(&m, &mut set) {
+                    return Some(i);
+                }
+            }
+
+            None
+        }
+
+        fn image(&self, path: &[(usize, usize)]) -> Image<Rgb> {
+            let mut image = Image::from_grid(&self.tiles, |tile| match tile {
+                Tile::Empty => (16, 16, 48),
+                Tile::Corrupted => (200, 40, 40),
+            });
+            path.iter().for_each(|pos| image.set(*pos, (80, 220, 80)));
+            image
+        }
     }
 
-    fn data(path: &str, size: (usize, usize)) -> (Memory, impl Iterator<Item = (usize, usize)>) {
+    fn parse(
+        lines: impl Iterator<Item = String>,
+        size: (usize, usize),
+    ) -> (Memory, impl Iterator<Item = (usize, usize)>) {
         let tiles = vec![vec![Tile::Empty; size.1]; size.0];
-        let it = read_by_line(path).map(|line| {
+        let it = lines.map(|line| {
             let mut parts = line
                 .trim()
                 .split(',')
@@ -121,6 +195,10 @@ mod tests {
         (Memory { size, tiles }, it)
     }
 
+    fn data(path: &str, size: (usize, usize)) -> (Memory, impl Iterator<Item = (usize, usize)>) {
+        parse(read_by_line(path), size)
+    }
+
     #[test]
     fn part1() {
         let (mut memory, falls) = data("tests/data/day18.input.txt", (71, 71));
@@ -128,21 +206,89 @@ mod tests {
         assert_eq!(memory.path().unwrap().len() - 1, 260);
     }
 
-    // Maybe not a very efficient way, but it works. And I'm tired today, so I won't optimize it anymore...
     #[test]
     fn part2() {
         let (memory, falls) = data("tests/data/day18.input.txt", (71, 71));
         let falls = falls.collect::<Vec<_>>();
-        let idx = (0..falls.len()).collect::<Vec<_>>();
-        let first_broken = idx.partition_point(|i| {
-            let mut m = memory.clone();
-            falls
-                .iter()
-                .take(*i + 1)
-                .for_each(|pos| m.byte_fall_at(*pos));
-            m.path().is_some()
-        });
+        let first_broken = memory.first_cut(&falls).unwrap();
         // This is (y, x), but in website we need input it as x,y
         assert_eq!(falls[first_broken], (48, 24));
     }
+
+    const EXAMPLE: &str = "5,4
+4,2
+4,5
+3,0
+2,1
+6,3
+2,4
+1,5
+0,6
+3,3
+2,6
+5,1
+1,2
+5,5
+2,5
+6,5
+1,4
+0,4
+6,4
+1,1
+6,1
+1,0
+0,5
+1,6
+2,0";
+
+    fn example() -> (Memory, impl Iterator<Item = (usize, usize)>) {
+        parse(EXAMPLE.lines().map(str::to_string), (7, 7))
+    }
+
+    #[test]
+    fn example_image() {
+        let (mut memory, falls) = example();
+        falls.take(12).for_each(|pos| memory.byte_fall_at(pos));
+        let path = memory.path().unwrap();
+        assert_eq!(path.len() - 1, 22);
+
+        let image = memory.image(&path);
+        assert_eq!(image.size(), (7, 7));
+        assert_eq!(image.get((0, 0)), Some((80, 220, 80)));
+        assert_eq!(image.get((4, 5)), Some((200, 40, 40)));
+        assert_eq!(image.upscale(3).get((14, 17)), Some((200, 40, 40)));
+    }
+
+    #[test]
+    fn example_first_cut() {
+        let (memory, falls) = example();
+        let falls = falls.collect::<Vec<_>>();
+        let first_broken = memory.first_cut(&falls).unwrap();
+        assert_eq!(falls[first_broken], (1, 6));
+
+        // Same answer as checking the path after every byte
+        let mut m = memory.clone();
+        let by_path = falls
+            .iter()
+            .position(|pos| {
+                m.byte_fall_at(*pos);
+                m.path().is_none()
+            })
+            .unwrap();
+        assert_eq!(first_broken, by_path);
+
+        assert_eq!(memory.first_cut(&falls[..12]), None);
+
+        // Bytes falling on the same tile again change nothing
+        let mut again = falls.clone();
+        again.insert(first_broken + 1, falls[0]);
+        again.push(falls[first_broken]);
+        assert_eq!(memory.first_cut(&again), Some(first_broken));
+        let mut again = falls[..12].to_vec();
+        again.extend_from_within(..6);
+        assert_eq!(memory.first_cut(&again), None);
+
+        let (small, _) = parse(std::iter::empty(), (2, 2));
+        assert_eq!(small.first_cut(&[(0, 1), (1, 0), (0, 1)]), Some(1));
+    }
 }
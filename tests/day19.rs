@@ -1,56 +1,209 @@
 #[cfg(test)]
 mod tests {
-    use std::{
-        cell::RefCell,
-        collections::{HashMap, HashSet},
-    };
-
     use advent2024::*;
 
     #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
     struct Pattern(String);
 
+    #[derive(Debug, Default)]
+    struct Node {
+        // 0 means no child, root is never a child
+        children: [usize; 26],
+        pattern: Option<usize>,
+    }
+
     #[derive(Debug)]
-    struct Patterns(HashSet<Pattern>);
+    struct Patterns {
+        patterns: Vec<Pattern>,
+        trie: Vec<Node>,
+    }
 
     impl Patterns {
+        fn new() -> Self {
+            Self {
+                patterns: vec![],
+                trie: vec![Node::default()],
+            }
+        }
+
+        fn insert(&mut self, pattern: Pattern) {
+            let mut node = 0;
+            for b in pattern.0.bytes() {
+                assert!(b.is_ascii_lowercase());
+                let idx = (b - b'a') as usize;
+                if self.trie[node].children[idx] == 0 {
+                    self.trie.push(Node::default());
+                    self.trie[node].children[idx] = self.trie.len() - 1;
+                }
+                node = self.trie[node].children[idx];
+            }
+            if self.trie[node].pattern.is_none() {
+                self.trie[node].pattern = Some(self.patterns.len());
+                self.patterns.push(pattern);
+            }
+        }
+
+        // (pattern index, end) of every pattern which starts at `start`, shortest first
+        fn matches<'a>(
+            &'a self,
+            logo: &'a [u8],
+            start: usize,
+        ) -> impl Iterator<Item = (usize, usize)> + 'a {
+            logo[start..]
+                .iter()
+                .enumerate()
+                .scan(0, move |node, (i, b)| {
+                    if !b.is_ascii_lowercase() {
+                        return None;
+                    }
+                    *node = self.trie[*node].children[(b - b'a') as usize];
+                    (*node != 0).then_some((*node, start + i + 1))
+                })
+                .filter_map(|(node, end)| Some((self.trie[node].pattern?, end)))
+        }
+
+        // ways[i] is how many decompositions `logo[i..]` has
+        fn ways(&self, logo: &str) -> Vec<usize> {
+            let logo = logo.as_bytes();
+            let mut ways = vec![0; logo.len() + 1];
+            ways[logo.len()] = 1;
+            for start in (0..logo.len()).rev() {
+                ways[start] = self.matches(logo, start).map(|(_, end)| ways[end]).sum();
+            }
+            ways
+        }
+
         pub fn check(&self, logo: &str) -> usize {
-            thread_local! {
-                static CACHE: RefCell<HashMap<String, usize>> = RefCell::default();
+            self.ways(logo)[0]
+        }
+
+        pub fn decompositions<'a>(&'a self, logo: &'a str) -> Decompositions<'a> {
+            let ways = self.ways(logo);
+            // Only keep choices which can still reach the end
+            let choices = (0..logo.len())
+                .map(|start| {
+                    self.matches(logo.as_bytes(), start)
+                        .filter(|(_, end)| ways[*end] > 0)
+                        .collect()
+                })
+                .collect();
+            let mut d = Decompositions {
+                patterns: self,
+                logo,
+                choices,
+                path: vec![],
+                done: ways[0] == 0,
+            };
+            if !d.done {
+                d.descend(0);
+            }
+            d
+        }
+
+        // Decomposition with the fewest patterns
+        pub fn shortest(&self, logo: &str) -> Option<Vec<&Pattern>> {
+            let bytes = logo.as_bytes();
+            // best[i] is (pieces, pattern index, end) for `logo[i..]`
+            let mut best: Vec<Option<(usize, usize, usize)>> = vec![None; bytes.len() + 1];
+            best[bytes.len()] = Some((0, usize::MAX, bytes.len()));
+            for start in (0..bytes.len()).rev() {
+                best[start] = self
+                    .matches(bytes, start)
+                    .filter_map(|(p, end)| Some((best[end]?.0 + 1, p, end)))
+                    .min_by_key(|(pieces, _, _)| *pieces);
+            }
+
+            let mut result = vec![];
+            let mut pos = 0;
+            while pos < bytes.len() {
+                let (_, p, end) = best[pos]?;
+                result.push(&self.patterns[p]);
+                pos = end;
             }
+            Some(result)
+        }
 
-            if logo.is_empty() {
-                1
-            } else {
-                let cr = CACHE.with_borrow(|cache| cache.get(logo).copied());
-                if let Some(r) = cr {
-                    r
-                } else {
-                    let ql = logo.len();
-                    let result = self
-                        .0
-                        .iter()
-                        .filter_map(|x| {
-                            let sp = ql.checked_sub(x.0.len());
-                            sp.filter(|sp| logo[*sp..] == x.0)
-                        })
-                        .map(|sp| self.check(&logo[..sp]))
-                        .sum();
-                    CACHE.with_borrow_mut(|cache| cache.insert(logo.to_owned(), result));
-                    result
+        // For an impossible logo, the first position no chain of patterns from the start covers
+        pub fn unmatched(&self, logo: &str) -> Option<usize> {
+            let bytes = logo.as_bytes();
+            let mut reachable = vec![false; bytes.len() + 1];
+            reachable[0] = true;
+            let mut furthest = 0;
+            for start in 0..bytes.len() {
+                if !reachable[start] {
+                    continue;
                 }
+                furthest = furthest.max(start);
+                self.matches(bytes, start)
+                    .for_each(|(_, end)| reachable[end] = true);
             }
+            (!reachable[bytes.len()]).then_some(furthest)
         }
     }
 
     impl FromIterator<Pattern> for Patterns {
         fn from_iter<T: IntoIterator<Item = Pattern>>(iter: T) -> Self {
-            Self(iter.into_iter().collect())
+            let mut patterns = Self::new();
+            iter.into_iter().for_each(|p| patterns.insert(p));
+            patterns
         }
     }
 
-    fn data(path: &str) -> (Patterns, impl Iterator<Item = String>) {
-        let mut lines = read_by_line(path);
+    // Lazy, every decomposition costs O(len) because dead ends are removed before
+    struct Decompositions<'a> {
+        patterns: &'a Patterns,
+        logo: &'a str,
+        choices: Vec<Vec<(usize, usize)>>,
+        // (start, index in choices[start])
+        path: Vec<(usize, usize)>,
+        done: bool,
+    }
+
+    impl Decompositions<'_> {
+        fn end(&self) -> usize {
+            self.path
+                .last()
+                .map_or(0, |(start, i)| self.choices[*start][*i].1)
+        }
+
+        fn descend(&mut self, mut pos: usize) {
+            while pos < self.logo.len() {
+                self.path.push((pos, 0));
+                pos = self.choices[pos][0].1;
+            }
+        }
+    }
+
+    impl<'a> Iterator for Decompositions<'a> {
+        type Item = Vec<&'a Pattern>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.done {
+                return None;
+            }
+
+            let result = self
+                .path
+                .iter()
+                .map(|(start, i)| &self.patterns.patterns[self.choices[*start][*i].0])
+                .collect();
+
+            // Advance to the next one
+            self.done = true;
+            while let Some((start, i)) = self.path.pop() {
+                if i + 1 < self.choices[start].len() {
+                    self.path.push((start, i + 1));
+                    self.descend(self.end());
+                    self.done = false;
+                    break;
+                }
+            }
+
+            Some(result)
+        }
+    }
+
+    fn parse(mut lines: impl Iterator<Item = String>) -> (Patterns, impl Iterator<Item = String>) {
         let one = lines.next().unwrap();
         let patterns = one
             .split(',')
@@ -59,13 +212,11 @@ mod tests {
 
         lines.next(); // skip empty line
 
-        (
-            patterns,
-            lines.map(|mut l| {
-                l.pop();
-                l
-            }),
-        )
+        (patterns, lines.map(|l| l.trim_end().to_owned()))
+    }
+
+    fn data(path: &str) -> (Patterns, impl Iterator<Item = String>) {
+        parse(read_by_line(path))
     }
 
     #[test]
@@ -86,4 +237,80 @@ mod tests {
         let result: usize = logos.map(|logo| patterns.check(&logo)).sum();
         assert_eq!(result, 692575723305545);
     }
+
+    const EXAMPLE: &str = "r, wr, b, g, bwu, rb, gb, br
+
+brwrr
+bggr
+gbbr
+rrbgbr
+ubwu
+bwurrg
+brgr
+bbrgwb";
+
+    fn example() -> (Patterns, Vec<String>) {
+        let (patterns, logos) = parse(EXAMPLE.lines().map(str::to_string));
+        (patterns, logos.collect())
+    }
+
+    fn joined(d: &[&Pattern]) -> String {
+        d.iter().map(|p| p.0.as_str()).collect::<Vec<_>>().join(",")
+    }
+
+    #[test]
+    fn example_counts() {
+        let (patterns, logos) = example();
+        let counts = logos.iter().map(|l| patterns.check(l)).collect::<Vec<_>>();
+        assert_eq!(counts, [2, 1, 4, 6, 0, 1, 2, 0]);
+        assert_eq!(counts.iter().filter(|c| **c > 0).count(), 6);
+        assert_eq!(counts.iter().sum::<usize>(), 16);
+
+        let impossible = logos
+            .iter()
+            .filter_map(|l| Some((l.as_str(), patterns.unmatched(l)?)))
+            .collect::<Vec<_>>();
+        assert_eq!(impossible, [("ubwu", 0), ("bbrgwb", 4)]);
+    }
+
+    #[test]
+    fn example_decompositions() {
+        let (patterns, _) = example();
+
+        let all = patterns
+            .decompositions("gbbr")
+            .map(|d| joined(&d))
+            .collect::<Vec<_>>();
+        assert_eq!(all, ["g,b,b,r", "g,b,br", "gb,b,r", "gb,br"]);
+
+        let all = patterns
+            .decompositions("rrbgbr")
+            .map(|d| joined(&d))
+            .collect::<Vec<_>>();
+        assert_eq!(all.len(), 6);
+        assert!(all.iter().all(|d| d.replace(',', "") == "rrbgbr"));
+        assert_eq!(patterns.decompositions("ubwu").count(), 0);
+        assert_eq!(patterns.decompositions("").count(), 1);
+
+        assert_eq!(joined(&patterns.shortest("gbbr").unwrap()), "gb,br");
+        assert_eq!(joined(&patterns.shortest("bwurrg").unwrap()), "bwu,r,r,g");
+        assert!(patterns.shortest("bbrgwb").is_none());
+    }
+
+    #[test]
+    fn many_decompositions() {
+        let patterns = ["a", "aa", "aaa"]
+            .into_iter()
+            .map(|s| Pattern(s.to_owned()))
+            .collect::<Patterns>();
+        let logo = "a".repeat(60);
+        let count = patterns.check(&logo);
+        assert!(count > 10usize.pow(15));
+        // Lazy, taking a few does not enumerate everything
+        let first = patterns.decompositions(&logo).take(3).collect::<Vec<_>>();
+        assert_eq!(first[0].len(), 60);
+        assert_eq!(first[1].len(), 59);
+        assert_eq!(patterns.shortest(&logo).unwrap().len(), 20);
+        assert_eq!(patterns.unmatched(&(logo + "b")), Some(60));
+    }
 }
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::{hash_map::Entry, HashMap, VecDeque};
+    use std::collections::{BTreeMap, VecDeque};
 
     use advent2024::*;
 
@@ -17,96 +17,82 @@ mod tests {
         map: Vec<Vec<Tile>>,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Cheat {
+        start: Pos,
+        end: Pos,
+        len: usize,
+        saved: usize,
+    }
+
     impl Maze {
-        pub fn race(
-            &self,
-            start: Pos,
-            end: Option<Pos>,
-            max: Option<usize>,
-        ) -> (Option<usize>, HashMap<Pos, usize>) {
-            let mut s = HashMap::<Pos, usize>::new();
-            let mut q = VecDeque::<(Pos, usize)>::new();
-
-            s.insert(start, 0);
-            q.push_back((start, 0));
-
-            while let Some((pos, score)) = q.pop_front() {
-                if max.is_some_and(|max| score > max) {
-                    continue;
-                }
-                if end.is_some_and(|end| pos == end) {
-                    return (Some(score), s);
-                }
+        // Steps from `from` to every tile, `None` for walls and unreachable tiles
+        fn distances(&self, from: Pos) -> Vec<Vec<Option<usize>>> {
+            let mut dist = vec![vec![None; self.size.1]; self.size.0];
+            let mut q = VecDeque::<Pos>::new();
 
-                let tile = self.map[pos.0][pos.1];
+            dist[from.0][from.1] = Some(0);
+            q.push_back(from);
 
-                match tile {
-                    Tile::Empty => {
-                        for neighbor in neighbors_limited(pos, self.size).into_iter().flatten() {
-                            let to_tile = self.map[neighbor.0][neighbor.1];
-                            let entry = s.entry(neighbor);
-                            if matches!(to_tile, Tile::Wall) || matches!(entry, Entry::Occupied(_))
-                            {
-                                continue;
-                            }
-                            entry.insert_entry(score + 1);
-                            q.push_back((neighbor, score + 1));
-                        }
+            while let Some(pos) = q.pop_front() {
+                let score = dist[pos.0][pos.1].unwrap();
+                for neighbor in neighbors_limited(pos, self.size).into_iter().flatten() {
+                    if matches!(self.map[neighbor.0][neighbor.1], Tile::Wall)
+                        || dist[neighbor.0][neighbor.1].is_some()
+                    {
+                        continue;
                     }
-                    Tile::Wall => unreachable!(),
+                    dist[neighbor.0][neighbor.1] = Some(score + 1);
+                    q.push_back(neighbor);
                 }
             }
 
-            (None, s)
+            dist
         }
 
-        fn cheat_points(&self, start: Pos, max_cheat: usize) -> Vec<(Pos, Pos, usize)> {
-            let mut result = Vec::new();
-            let max_cheat_isize = max_cheat as isize;
+        pub fn race(&self, start: Pos, end: Pos) -> Option<usize> {
+            self.distances(start)[end.0][end.1]
+        }
 
-            for row in 0..self.size.0 {
-                for col in 0..self.size.1 {
-                    if matches!(self.map[row][col], Tile::Wall) {
-                        continue;
-                    }
+        // Every cheat no longer than `max_cheat` which saves some time
+        fn cheats(&self, start: Pos, end: Pos, max_cheat: usize) -> Vec<Cheat> {
+            let from_start = self.distances(start);
+            let to_end = self.distances(end);
+            let Some(no_cheat) = from_start[end.0][end.1] else {
+                return vec![];
+            };
+            let max = max_cheat as isize;
 
-                    let cheat_start = (row, col);
-                    let Some(start_to_cheat_start) = self.race(start, Some(cheat_start), None).0
-                    else {
+            let mut result = Vec::new();
+            for (row, line) in from_start.iter().enumerate() {
+                for (col, before) in line.iter().enumerate() {
+                    let Some(before) = *before else {
                         continue;
                     };
-
-                    let reachable = self.race(cheat_start, None, Some(max_cheat)).1;
-
-                    for ox in -max_cheat_isize..=max_cheat_isize {
-                        let y_offset_max = max_cheat_isize - ox.abs();
-                        for oy in -y_offset_max..=y_offset_max {
-                            let cheat_len = ox.unsigned_abs() + oy.unsigned_abs();
-                            if cheat_len != 0 {
-                                let cheat_end_y = row as isize + oy;
-                                let cheat_end_x = col as isize + ox;
-                                if cheat_end_x >= 0
-                                    && cheat_end_y >= 0
-                                    && cheat_end_y < self.size.0 as isize
-                                    && cheat_end_x < self.size.1 as isize
-                                {
-                                    let cheat_end = (cheat_end_y as usize, cheat_end_x as usize);
-
-                                    if matches!(self.map[cheat_end.0][cheat_end.1], Tile::Wall) {
-                                        continue;
-                                    }
-
-                                    if reachable
-                                        .get(&cheat_end)
-                                        .is_none_or(|score| cheat_len < *score)
-                                    {
-                                        result.push((
-                                            cheat_start,
-                                            cheat_end,
-                                            start_to_cheat_start + cheat_len,
-                                        ));
-                                    }
-                                }
+                    // Every tile in the Manhattan diamond around the cheat start
+                    for oy in -max..=max {
+                        let x_offset_max = max - oy.abs();
+                        for ox in -x_offset_max..=x_offset_max {
+                            let (Some(r), Some(c)) = (
+                                row.checked_add_signed(oy).filter(|r| *r < self.size.0),
+                                col.checked_add_signed(ox).filter(|c| *c < self.size.1),
+                            ) else {
+                                continue;
+                            };
+                            let Some(after) = to_end[r][c] else {
+                                continue;
+                            };
+                            let len = oy.unsigned_abs() + ox.unsigned_abs();
+                            if let Some(saved) = no_cheat
+                                .checked_sub(before + len + after)
+                                .filter(|saved| *saved > 0)
+                            {
+                                result.push(Cheat {
+                                    start: (row, col),
+                                    end: (r, c),
+                                    len,
+                                    saved,
+                                });
                             }
                         }
                     }
@@ -116,35 +102,33 @@ mod tests {
             result
         }
 
-        fn cheat_saves(
+        // Saved time -> how many cheats save exactly that much
+        pub fn histogram(&self, start: Pos, end: Pos, max_cheat: usize) -> BTreeMap<usize, usize> {
+            let mut histogram = BTreeMap::new();
+            self.cheats(start, end, max_cheat)
+                .into_iter()
+                .for_each(|cheat| *histogram.entry(cheat.saved).or_default() += 1);
+            histogram
+        }
+
+        pub fn count_saving(
             &self,
             start: Pos,
             end: Pos,
             max_cheat: usize,
-            no_cheat_score: usize,
-        ) -> Vec<(Pos, Pos, usize)> {
-            let mut to_end_scores = HashMap::<Pos, Option<usize>>::new();
-
-            self.cheat_points(start, max_cheat)
-                .into_iter()
-                .filter_map(|(cheat_start, cheat_end, start_to_cheat_end_score)| {
-                    to_end_scores
-                        .entry(cheat_end)
-                        .or_insert_with(|| self.race(cheat_end, Some(end), None).0)
-                        .and_then(|cheat_end_to_end_score| {
-                            no_cheat_score
-                                .checked_sub(start_to_cheat_end_score + cheat_end_to_end_score)
-                        })
-                        .map(|saved| (cheat_start, cheat_end, saved))
-                })
-                .collect()
+            at_least: usize,
+        ) -> usize {
+            self.histogram(start, end, max_cheat)
+                .range(at_least..)
+                .map(|(_, count)| count)
+                .sum()
         }
     }
 
-    fn data(path: &str) -> (Pos, Pos, Maze) {
+    fn parse(lines: impl Iterator<Item = String>) -> (Pos, Pos, Maze) {
         let mut start = (0, 0);
         let mut end = (0, 0);
-        let map: Vec<_> = read_by_line(path)
+        let map: Vec<_> = lines
             .enumerate()
             .map(|(row, line)| {
                 line.into_bytes()
@@ -178,14 +162,13 @@ mod tests {
         )
     }
 
+    fn data(path: &str) -> (Pos, Pos, Maze) {
+        parse(read_by_line(path))
+    }
+
     fn cheat_save_more_then_100(max_cheat: usize) -> usize {
         let (start, end, maze) = data("tests/data/day20.input.txt");
-        let no_cheat = maze.race(start, Some(end), None).0.unwrap();
-        let cheats_saves = maze.cheat_saves(start, end, max_cheat, no_cheat);
-        cheats_saves
-            .into_iter()
-            .filter(|(_, _, x)| *x >= 100)
-            .count()
+        maze.count_saving(start, end, max_cheat, 100)
     }
 
     #[test]
@@ -197,4 +180,83 @@ mod tests {
     fn part2() {
         assert_eq!(cheat_save_more_then_100(20), 989316);
     }
+
+    const EXAMPLE: &str = "###############
+#...#...#.....#
+#.#.#.#.#.###.#
+#S#...#.#.#...#
+#######.#.#.###
+#######.#.#...#
+#######.#.###.#
+###..E#...#...#
+###.#######.###
+#...###...#...#
+#.#####.#.###.#
+#.#...#.#.#...#
+#.#.#.#.#.#.###
+#...#...#...###
+###############";
+
+    #[test]
+    fn example_histogram() {
+        let (start, end, maze) = parse(EXAMPLE.lines().map(str::to_string));
+        assert_eq!(maze.race(start, end), Some(84));
+
+        let histogram = maze.histogram(start, end, 2);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [
+                (2, 14),
+                (4, 14),
+                (6, 2),
+                (8, 4),
+                (10, 2),
+                (12, 3),
+                (20, 1),
+                (36, 1),
+                (38, 1),
+                (40, 1),
+                (64, 1)
+            ]
+        );
+
+        let histogram = maze.histogram(start, end, 20);
+        assert_eq!(
+            histogram.range(50..).collect::<Vec<_>>(),
+            [
+                (&50, &32),
+                (&52, &31),
+                (&54, &29),
+                (&56, &39),
+                (&58, &25),
+                (&60, &23),
+                (&62, &20),
+                (&64, &19),
+                (&66, &12),
+                (&68, &14),
+                (&70, &12),
+                (&72, &22),
+                (&74, &4),
+                (&76, &3)
+            ]
+        );
+        assert_eq!(maze.count_saving(start, end, 20, 50), 285);
+    }
+
+    #[test]
+    fn cheat_lengths() {
+        let (start, end, maze) = parse(EXAMPLE.lines().map(str::to_string));
+        let cheats = maze.cheats(start, end, 20);
+        assert!(cheats.iter().all(|c| c.len <= 20 && c.saved > 0));
+        // A longer limit only adds cheats
+        assert_eq!(
+            cheats.iter().filter(|c| c.len <= 2).count(),
+            maze.cheats(start, end, 2).len()
+        );
+        assert_eq!(maze.count_saving(start, end, 0, 1), 0);
+        // The best one goes straight through the wall from start towards the end
+        let best = cheats.iter().max_by_key(|c| c.saved).unwrap();
+        assert_eq!(best.saved, 76);
+        assert_eq!(best.start, start);
+    }
 }
@@ -1,289 +1,373 @@
 #[cfg(test)]
 mod tests {
-    use std::{
-        cmp::Ordering,
-        collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
-        hash::Hash,
-        sync::LazyLock,
-    };
+    use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
 
     use advent2024::*;
 
-    trait Keyboard: Sized + Eq + Copy + Hash {
-        const GAP: Self;
+    // Same order as `neighbors` returns
+    const ARROWS: [u8; 4] = *b"<>^v";
+    const ACTIVATE: u8 = b'A';
 
-        fn mapping() -> Vec<Vec<Self>>;
+    const NUMERIC: &str = "789\n456\n123\n 0A";
+    const DIRECTIONAL: &str = " ^A\n<v>";
 
-        fn path_cost(path: &[Controller]) -> usize;
+    #[derive(Debug, Clone)]
+    struct Keypad {
+        grid: Vec<Vec<u8>>,
+        gap: u8,
+        start: u8,
+        keys: HashMap<u8, (usize, usize)>,
+    }
+
+    impl Keypad {
+        fn parse(layout: &str, gap: char) -> Self {
+            let grid: Vec<Vec<u8>> = layout.lines().map(|line| line.bytes().collect()).collect();
+            let gap = gap as u8;
+            let keys = grid
+                .iter()
+                .enumerate()
+                .flat_map(|(row, line)| {
+                    line.iter()
+                        .copied()
+                        .enumerate()
+                        .map(move |(col, key)| (key, (row, col)))
+                })
+                .filter(|(key, _)| *key != gap)
+                .collect();
+
+            Self {
+                grid,
+                gap,
+                start: ACTIVATE,
+                keys,
+            }
+        }
 
-        fn path_cache() -> HashMap<(Self, Self), Vec<Controller>> {
-            let m = Self::mapping();
-            let size = (m.len(), m[0].len());
-            let from_points = m.iter().enumerate().flat_map(|(row, line)| {
-                line.iter()
-                    .copied()
-                    .enumerate()
-                    .map(move |(col, button)| ((row, col), button))
-            });
+        fn start(mut self, key: u8) -> Self {
+            assert!(self.keys.contains_key(&key));
+            self.start = key;
+            self
+        }
 
-            let mut result: HashMap<(Self, Self), HashSet<Vec<Controller>>> = HashMap::new();
+        fn size(&self) -> (usize, usize) {
+            let width = self.grid.iter().map(|line| line.len()).max().unwrap_or(0);
+            (self.grid.len(), width)
+        }
+
+        fn key(&self, (row, col): (usize, usize)) -> Option<u8> {
+            self.grid
+                .get(row)
+                .and_then(|line| line.get(col))
+                .copied()
+                .filter(|key| *key != self.gap)
+        }
 
-            for (pos, from_button) in from_points {
-                let mut q: VecDeque<((usize, usize), Vec<Controller>)> = VecDeque::new();
+        fn is_controller(&self) -> bool {
+            self.start == ACTIVATE && ARROWS.iter().all(|key| self.keys.contains_key(key))
+        }
+
+        // All shortest arrow sequences between every two keys, never passing a gap
+        fn paths(&self) -> HashMap<(u8, u8), HashSet<Vec<u8>>> {
+            let mut result: HashMap<(u8, u8), HashSet<Vec<u8>>> = HashMap::new();
+
+            for (&from_key, &pos) in self.keys.iter() {
+                let mut q: VecDeque<((usize, usize), Vec<u8>)> = VecDeque::new();
                 q.push_back((pos, vec![]));
                 while let Some((pos, path)) = q.pop_front() {
-                    let button = m[pos.0][pos.1];
-
-                    if button == Self::GAP {
+                    let Some(key) = self.key(pos) else {
                         continue;
-                    }
+                    };
 
-                    let entry = result.entry((from_button, button));
-
-                    match entry {
+                    match result.entry((from_key, key)) {
                         Entry::Occupied(mut e) => {
-                            match path.len().cmp(&e.get().iter().next().unwrap().len()) {
-                                Ordering::Equal => {
-                                    e.get_mut().insert(path.clone());
-                                }
-                                Ordering::Less => {
-                                    unreachable!()
-                                }
-                                Ordering::Greater => {
-                                    continue;
-                                }
+                            if path.len() > e.get().iter().next().unwrap().len() {
+                                continue;
                             }
+                            e.get_mut().insert(path.clone());
                         }
                         Entry::Vacant(e) => {
                             e.insert(Some(path.clone()).into_iter().collect());
                         }
                     }
 
-                    for (idx, neighbor) in neighbors_limited(pos, size).into_iter().enumerate() {
+                    for (idx, neighbor) in
+                        neighbors_limited(pos, self.size()).into_iter().enumerate()
+                    {
                         let Some(neighbor) = neighbor else { continue };
-                        let path = path.iter().copied().chain(Some(unsafe {
-                            std::mem::transmute::<u8, Controller>(idx as u8)
-                        }));
+                        let path = path.iter().copied().chain(Some(ARROWS[idx]));
                         q.push_back((neighbor, path.collect()));
                     }
                 }
             }
 
             result
-                .into_iter()
-                .map(|(k, v)| {
-                    (
-                        k,
-                        v.into_iter()
-                            .min_by_key(|path| Self::path_cost(path))
-                            .unwrap(),
-                    )
-                })
-                .collect()
         }
     }
 
-    static NUM_PAD_CACHE: LazyLock<HashMap<(NumPad, NumPad), Vec<Controller>>> = LazyLock::new(NumPad::path_cache);
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    #[repr(u8)]
-    enum NumPad {
-        Zero,
-        One,
-        Two,
-        Three,
-        Four,
-        Five,
-        Six,
-        Seven,
-        Eight,
-        Nine,
-        Active,
-        Gap,
+    // layers[0] types the code, layers[i + 1] controls layers[i], the last one is pressed by human
+    struct KeypadChain {
+        layers: Vec<Keypad>,
+        costs: Vec<HashMap<(u8, u8), usize>>,
+        // Cheapest arrow sequence between two keys, for every layer except the last
+        best: Vec<HashMap<(u8, u8), Vec<u8>>>,
     }
 
-    impl Keyboard for NumPad {
-        const GAP: Self = Self::Gap;
+    impl KeypadChain {
+        fn new(layers: Vec<Keypad>) -> Self {
+            assert!(!layers.is_empty());
+            assert!(layers[1..].iter().all(Keypad::is_controller));
 
-        fn mapping() -> Vec<Vec<Self>> {
-            vec![
-                vec![Self::Seven, Self::Eight, Self::Nine],
-                vec![Self::Four, Self::Five, Self::Six],
-                vec![Self::One, Self::Two, Self::Three],
-                vec![Self::Gap, Self::Zero, Self::Active],
-            ]
-        }
+            let human = layers.last().unwrap();
+            let mut costs = vec![human
+                .keys
+                .keys()
+                .flat_map(|&from| human.keys.keys().map(move |&to| ((from, to), 1)))
+                .collect::<HashMap<_, _>>()];
 
-        fn path_cost(path: &[Controller]) -> usize {
-            let mut path = path.to_owned();
-            // 4 iter is enough to chose the most efficient move
-            for _ in 0..4 {
-                path = Controller::moves(Controller::Active, &path, Some(Controller::Active));
+            let mut best = vec![];
+
+            for layer in layers.iter().rev().skip(1) {
+                let upper = costs.last().unwrap();
+                let (cost, path) = layer
+                    .paths()
+                    .into_iter()
+                    .map(|(pair, paths)| {
+                        let (cost, path) = paths
+                            .into_iter()
+                            .map(|path| (Self::sequence_cost(upper, ACTIVATE, &path), path))
+                            .min()
+                            .unwrap();
+                        ((pair, cost), (pair, path))
+                    })
+                    .unzip();
+                costs.push(cost);
+                best.push(path);
             }
-            path.len()
-        }
-    }
 
-    impl NumPad {
-        pub fn one_move_times(from: Self, to: Self, middle_levels: usize) -> usize {
-            let mut result = HashMap::new();
-            let to = NUM_PAD_CACHE
-                .get(&(from, to)).unwrap().iter().copied().chain(Some(Controller::Active));
-            let from = Some(Controller::Active).into_iter().chain(to.clone());
-            from.zip(to).for_each(|(f, t)| {
-                *result.entry((f, t)).or_default() += 1;
-            });
-
-            for _ in 0..middle_levels {
-                result = Controller::move_times(result);
+            costs.reverse();
+            best.reverse();
+            Self {
+                layers,
+                costs,
+                best,
             }
+        }
 
-            result.values().sum()
+        fn robots(target: Keypad, controller: Keypad, middle_levels: usize) -> Self {
+            let mut layers = vec![target];
+            layers.extend(std::iter::repeat_n(controller, middle_levels + 1));
+            Self::new(layers)
         }
 
-        fn move_times(start: Self, code: &[Self], middle_levels: usize) -> usize {
-            let from = Some(&start).into_iter().chain(code.iter()).copied();
-            let to = code.iter().copied();
-            let points = from.zip(to);
-            points
-                .map(|(from, to)| Self::one_move_times(from, to, middle_levels))
-                .sum()
+        // Human presses of typing `keys` then `ACTIVATE` on a layer, pointer starts at `start`
+        fn sequence_cost(costs: &HashMap<(u8, u8), usize>, start: u8, keys: &[u8]) -> usize {
+            let to = keys.iter().copied().chain(Some(ACTIVATE));
+            let from = Some(start).into_iter().chain(to.clone());
+            from.zip(to).map(|pair| costs[&pair]).sum()
         }
-    }
 
-    static CTRL_CACHE: LazyLock<HashMap<(Controller, Controller), Vec<Controller>>> = LazyLock::new(Controller::path_cache);
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    #[repr(u8)]
-    enum Controller {
-        Left,
-        Right,
-        Up,
-        Down,
-        Active,
-        Gap,
-    }
+        // `None` if the code has a key which is not on the first keypad
+        fn presses(&self, code: &[u8]) -> Option<usize> {
+            let from = Some(self.layers[0].start)
+                .into_iter()
+                .chain(code.iter().copied());
+            from.zip(code.iter().copied())
+                .map(|pair| self.costs[0].get(&pair).copied())
+                .sum()
+        }
 
-    impl Keyboard for Controller {
-        const GAP: Self = Self::Gap;
+        // One optimal sequence of keys pressed on `layer` to type the code, generated lazily
+        // because it is far too long to store for deep chains
+        fn keys<'a>(&'a self, code: &'a [u8], layer: usize) -> Box<dyn Iterator<Item = u8> + 'a> {
+            if layer == 0 {
+                return Box::new(code.iter().copied());
+            }
 
-        fn mapping() -> Vec<Vec<Self>> {
-            vec![
-                vec![Self::GAP, Self::Up, Self::Active],
-                vec![Self::Left, Self::Down, Self::Right],
-            ]
-        }
-        
-        // We don't need a cost function because the path_cache is hardcoded
-        fn path_cost(_path: &[Controller]) -> usize {
-            unimplemented!()
-        }
-        
-        #[rustfmt::skip]
-        fn path_cache() -> HashMap<(Self, Self), Vec<Controller>> {
-            vec![
-                ((Self::Active, Self::Active), vec![]),
-                ((Self::Active, Self::Left), vec![Self::Down, Self::Left, Self::Left]),
-                ((Self::Active, Self::Right), vec![Self::Down]),
-                ((Self::Active, Self::Up), vec![Self::Left]),
-                ((Self::Active, Self::Down), vec![Self::Left, Self::Down]),
-
-                ((Self::Left, Self::Active), vec![Self::Right, Self::Right, Self::Up]),
-                ((Self::Left, Self::Left), vec![]),
-                ((Self::Left, Self::Right), vec![Self::Right, Self::Right]),
-                ((Self::Left, Self::Up), vec![Self::Right, Self::Up]),
-                ((Self::Left, Self::Down), vec![Self::Right]),
-                
-                ((Self::Right, Self::Active), vec![Self::Up]),
-                ((Self::Right, Self::Left), vec![Self::Left, Self::Left]),
-                ((Self::Right, Self::Right), vec![]),
-                ((Self::Right, Self::Up), vec![Self::Left, Self::Up]),
-                ((Self::Right, Self::Down), vec![Self::Left]),
-
-                ((Self::Up, Self::Active), vec![Self::Right]),
-                ((Self::Up, Self::Left), vec![Self::Down, Self::Left]),
-                ((Self::Up, Self::Right), vec![Self::Down, Self::Right]),
-                ((Self::Up, Self::Up), vec![]),
-                ((Self::Up, Self::Down), vec![Self::Down]),
-
-                ((Self::Down, Self::Active), vec![Self::Up, Self::Right]),
-                ((Self::Down, Self::Left), vec![Self::Left]),
-                ((Self::Down, Self::Right), vec![Self::Right]),
-                ((Self::Down, Self::Up), vec![Self::Up]),
-                ((Self::Down, Self::Down), vec![]),
-            ].into_iter().collect()
+            let best = &self.best[layer - 1];
+            let start = self.layers[layer - 1].start;
+            Box::new(
+                self.keys(code, layer - 1)
+                    .scan(start, |from, to| Some((std::mem::replace(from, to), to)))
+                    .flat_map(|pair| best[&pair].iter().copied().chain(Some(ACTIVATE))),
+            )
         }
-    }
 
-    impl Controller {
-        fn moves(start: Self, code: &[Self], end: Option<Self>) -> Vec<Controller> {
-            let from = Some(&start).into_iter().chain(code.iter()).copied();
-            let to = code.iter().copied().chain(end);
-            let points = from.zip(to);
-            points
-                .flat_map(|(from, to)| {
-                    CTRL_CACHE
-                        .get(&(from, to))
-                        .unwrap()
-                        .iter()
-                        .copied()
-                        .chain(Some(Self::Active))
-                })
-                .collect()
+        fn human_keys<'a>(&'a self, code: &'a [u8]) -> impl Iterator<Item = u8> + 'a {
+            self.keys(code, self.layers.len() - 1)
         }
 
-        fn move_times(code: HashMap<(Self, Self), usize>) -> HashMap<(Self, Self), usize> {
-            let mut result = HashMap::new();
-
-            for ((from, to), times) in code {
-                let to = CTRL_CACHE
-                    .get(&(from, to))
-                    .unwrap()
-                    .iter()
-                    .copied()
-                    .chain(Some(Self::Active));
-                let from = Some(Self::Active).into_iter().chain(to.clone());
-                from.zip(to).for_each(|(f, t)| {
-                    *result.entry((f, t)).or_default() += times;
-                });
+        // Keys pressed on `layer`, returns what is typed on the first keypad, or `None` if any
+        // robot arm points to a gap, or a key has no meaning
+        fn simulate(&self, layer: usize, keys: impl IntoIterator<Item = u8>) -> Option<Vec<u8>> {
+            let mut pointers = self.layers[..layer]
+                .iter()
+                .map(|keypad| keypad.keys[&keypad.start])
+                .collect::<Vec<_>>();
+            let mut typed = vec![];
+
+            for mut key in keys {
+                if !self.layers[layer].keys.contains_key(&key) {
+                    return None;
+                }
+                for below in (0..layer).rev() {
+                    let pos = &mut pointers[below];
+                    if key == ACTIVATE {
+                        key = self.layers[below].key(*pos)?;
+                        if below == 0 {
+                            typed.push(key);
+                        }
+                        continue;
+                    }
+
+                    let idx = ARROWS.iter().position(|arrow| *arrow == key)?;
+                    let size = self.layers[below].size();
+                    *pos = neighbors_limited(*pos, size)[idx]?;
+                    self.layers[below].key(*pos)?;
+                    break;
+                }
+                if layer == 0 {
+                    typed.push(key);
+                }
             }
 
-            result
+            Some(typed)
         }
     }
 
-    fn data(path: &str) -> impl Iterator<Item = (usize, Vec<NumPad>)> {
+    // Run-length encode a key sequence, e.g. `<<vA` to `[(<, 2), (v, 1), (A, 1)]`
+    fn runs(keys: impl Iterator<Item = u8>) -> impl Iterator<Item = (u8, usize)> {
+        let mut keys = keys.peekable();
+        std::iter::from_fn(move || {
+            let key = keys.next()?;
+            let mut count = 1;
+            while keys.next_if_eq(&key).is_some() {
+                count += 1;
+            }
+            Some((key, count))
+        })
+    }
+
+    fn data(path: &str) -> impl Iterator<Item = (usize, Vec<u8>)> {
         read_by_line(path).map(|line| {
+            let line = line.trim();
             (
-                line[..line.len() - 2].parse().unwrap(),
-                line.into_bytes()
-                    .into_iter()
-                    .filter_map(|c| match c {
-                        b'0'..=b'9' => Some(c - b'0'),
-                        b'A' => Some(10),
-                        _ => None,
-                    })
-                    .map(|n| unsafe { std::mem::transmute::<u8, NumPad>(n) })
-                    .collect::<Vec<_>>(),
+                line[..line.len() - 1].parse().unwrap(),
+                line.as_bytes().to_vec(),
             )
         })
     }
 
+    fn complexity(chain: &KeypadChain, codes: impl Iterator<Item = (usize, Vec<u8>)>) -> usize {
+        codes
+            .map(|(num, code)| chain.presses(&code).unwrap() * num)
+            .sum()
+    }
+
+    fn standard(middle_levels: usize) -> KeypadChain {
+        KeypadChain::robots(
+            Keypad::parse(NUMERIC, ' '),
+            Keypad::parse(DIRECTIONAL, ' '),
+            middle_levels,
+        )
+    }
+
     #[test]
     fn part1() {
-        let result: usize = data("tests/data/day21.input.txt")
-            .map(|(num, code)| NumPad::move_times(NumPad::Active, &code, 2) * num)
-            .sum();
+        let result = complexity(&standard(2), data("tests/data/day21.input.txt"));
 
         assert_eq!(result, 184716);
     }
 
     #[test]
     fn part2() {
-        let result: usize = data("tests/data/day21.input.txt")
-            .map(|(num, code)| NumPad::move_times(NumPad::Active, &code, 25) * num)
-            .sum();
+        let result = complexity(&standard(25), data("tests/data/day21.input.txt"));
 
         assert_eq!(result, 229403562787554);
     }
+
+    const EXAMPLE: [&str; 5] = ["029A", "980A", "179A", "456A", "379A"];
+
+    fn example() -> impl Iterator<Item = (usize, Vec<u8>)> {
+        EXAMPLE
+            .iter()
+            .map(|code| (code[..3].parse().unwrap(), code.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn example_chain_depth() {
+        let code = b"029A";
+        let direct = KeypadChain::new(vec![Keypad::parse(NUMERIC, ' ')]);
+        assert_eq!(direct.presses(code), Some(4));
+        assert_eq!(standard(0).presses(code), Some(12));
+        assert_eq!(standard(1).presses(code), Some(28));
+        assert_eq!(standard(2).presses(code), Some(68));
+
+        assert_eq!(complexity(&standard(2), example()), 126384);
+        assert_eq!(complexity(&standard(25), example()), 154115708116294);
+    }
+
+    #[test]
+    fn custom_layouts() {
+        let phone = Keypad::parse("123\n456\n789\n*0#", '.').start(b'0');
+        let chain = KeypadChain::robots(phone, Keypad::parse(DIRECTIONAL, ' '), 0);
+        // 0 -> 1: ^^^<A, 1 -> 9: vv>>A or >>vvA
+        assert_eq!(chain.presses(b"1"), Some(5));
+        assert_eq!(chain.presses(b"19"), Some(10));
+
+        // Upside-down numeric pad, the ` 0A` row is on the top instead of the bottom
+        let flipped = Keypad::parse(" 0A\n123\n456\n789", ' ');
+        let chain = KeypadChain::robots(flipped, Keypad::parse(DIRECTIONAL, ' '), 2);
+        assert_eq!(chain.presses(b"0A"), standard(2).presses(b"0A"));
+        assert_ne!(chain.presses(b"029A"), standard(2).presses(b"029A"));
+        assert_eq!(chain.presses(b"0B"), None);
+    }
+
+    #[test]
+    fn example_sequences() {
+        let chain = standard(2);
+        let code = b"029A";
+
+        let layers = (0..=3)
+            .map(|layer| chain.keys(code, layer).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(layers[0], code);
+        assert_eq!(
+            layers.iter().map(Vec::len).collect::<Vec<_>>(),
+            [4, 12, 28, 68]
+        );
+        for (layer, keys) in layers.iter().enumerate() {
+            assert_eq!(chain.simulate(layer, keys.iter().copied()).unwrap(), code);
+        }
+
+        for (_, code) in example() {
+            let keys = chain.human_keys(&code).collect::<Vec<_>>();
+            assert_eq!(Some(keys.len()), chain.presses(&code));
+            assert_eq!(chain.simulate(3, keys).unwrap(), code);
+        }
+
+        // One of the examples given by the puzzle
+        let given = b"<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A";
+        assert_eq!(chain.simulate(3, given.iter().copied()).unwrap(), code);
+
+        // Robot arm points to the gap
+        assert!(chain.simulate(1, *b"<<A").is_none());
+        assert!(chain.simulate(1, *b"0").is_none());
+    }
+
+    #[test]
+    fn deep_chain_sequence() {
+        let chain = standard(25);
+        let code = b"029A";
+
+        let human = runs(chain.human_keys(code)).take(1000).collect::<Vec<_>>();
+        assert_eq!(human.len(), 1000);
+        assert!(human.windows(2).all(|w| w[0].0 != w[1].0));
+
+        // The first press of the code only needs a prefix of the human keys
+        let chain = standard(10);
+        let prefix = runs(chain.human_keys(code))
+            .flat_map(|(key, count)| std::iter::repeat_n(key, count))
+            .take(chain.presses(&code[..1]).unwrap());
+        assert_eq!(chain.simulate(11, prefix).unwrap(), b"0");
+    }
 }
@@ -2,7 +2,7 @@
 mod tests {
     use std::collections::{hash_map::Entry, HashMap, VecDeque};
 
-    use advent2024::*;
+    use advent2024::{counter::Counter, *};
 
     fn secrets(mut init: u64) -> impl Iterator<Item = u64> {
         Some(init).into_iter().chain(std::iter::from_fn(move || {
@@ -82,17 +82,14 @@ mod tests {
 
     #[test]
     fn part2() {
-        let mut result = HashMap::<VecDeque<i8>, usize>::new();
-
-        data("tests/data/day22.input.txt")
-            .map(bananas)
-            .for_each(|record| {
-                for (diff, price) in record {
-                    *result.entry(diff).or_default() += price as usize;
-                }
-            });
-
-        let max = result.values().max().copied().unwrap();
+        let result = data("tests/data/day22.input.txt").map(bananas).fold(
+            Counter::new(),
+            |result, record| {
+                result + Counter::from_counts(record.into_iter().map(|(d, p)| (d, p as usize)))
+            },
+        );
+
+        let max = result.most_common(1)[0].1;
 
         assert_eq!(max, 1791);
     }
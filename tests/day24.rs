@@ -2,16 +2,22 @@
 mod tests {
     use std::{
         collections::{HashMap, HashSet, VecDeque},
+        fmt::Display,
         str::FromStr,
     };
 
     use advent2024::*;
 
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     enum LogicGate {
         And,
         Or,
         Xor,
+        Nand,
+        Nor,
+        Xnor,
+        // Unary, stored in connections as `(input, Not, input)`
+        Not,
     }
 
     impl FromStr for LogicGate {
@@ -22,17 +28,39 @@ mod tests {
                 "AND" => Self::And,
                 "OR" => Self::Or,
                 "XOR" => Self::Xor,
+                "NAND" => Self::Nand,
+                "NOR" => Self::Nor,
+                "XNOR" => Self::Xnor,
+                "NOT" => Self::Not,
                 _ => Err(())?,
             })
         }
     }
 
+    impl Display for LogicGate {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(match self {
+                Self::And => "AND",
+                Self::Or => "OR",
+                Self::Xor => "XOR",
+                Self::Nand => "NAND",
+                Self::Nor => "NOR",
+                Self::Xnor => "XNOR",
+                Self::Not => "NOT",
+            })
+        }
+    }
+
     impl LogicGate {
         pub fn logic(&self, lhs: bool, rhs: bool) -> bool {
             match self {
                 Self::And => lhs && rhs,
                 Self::Or => lhs || rhs,
                 Self::Xor => lhs != rhs,
+                Self::Nand => !(lhs && rhs),
+                Self::Nor => !(lhs || rhs),
+                Self::Xnor => lhs == rhs,
+                Self::Not => !lhs,
             }
         }
     }
@@ -44,6 +72,8 @@ mod tests {
     #[derive(Debug, Clone)]
     struct Device {
         bit_count: usize,
+        output_bits: usize,
+        initial: HashMap<String, bool>,
         wires: HashMap<String, bool>,
         connections: HashMap<String, (String, LogicGate, String)>,
     }
@@ -78,7 +108,7 @@ mod tests {
         fn output(&mut self) -> Option<Vec<bool>> {
             (0..=99)
                 .map(|x| format!("z{:02}", x))
-                .take(self.bit_count)
+                .take(self.output_bits)
                 .map(|wire| self.get(wire))
                 .collect()
         }
@@ -169,43 +199,235 @@ mod tests {
         }
     }
 
-    fn data(path: &str) -> Device {
-        let mut iter = read_by_line(path);
-
-        let wires: HashMap<_, _> = iter
-            .by_ref()
-            .take_while(|s| !s.trim().is_empty())
-            .map(|line| {
-                let mut parts = line.trim().splitn(2, ": ");
-                let wire = parts.next().unwrap().to_string();
-                let value = matches!(parts.next().unwrap(), "1");
-                (wire, value)
-            })
-            .collect();
-
-        let connections = iter
-            .map(|line| {
-                let mut parts = line.trim().split(' ');
-                let l = parts.next().unwrap().to_string();
-                let gate = parts.next().unwrap().parse::<LogicGate>().unwrap();
-                let r = parts.next().unwrap().to_string();
-                let _ = parts.next().unwrap();
-                let output = parts.next().unwrap().to_string();
-                (output, (l, gate, r))
+    impl FromStr for Device {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut iter = s.lines();
+
+            let initial = iter
+                .by_ref()
+                .take_while(|s| !s.trim().is_empty())
+                .map(|line| {
+                    let (wire, value) = line.trim().split_once(": ").ok_or(())?;
+                    let value = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => Err(())?,
+                    };
+                    Ok((wire.to_string(), value))
+                })
+                .collect::<Result<HashMap<_, _>, _>>()?;
+
+            let connections = iter
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let parts = line.trim().split(' ').collect::<Vec<_>>();
+                    Ok(match parts[..] {
+                        ["NOT", input, "->", output] => (
+                            output.to_string(),
+                            (input.to_string(), LogicGate::Not, input.to_string()),
+                        ),
+                        [l, gate, r, "->", output] => {
+                            let gate = gate.parse::<LogicGate>()?;
+                            if gate == LogicGate::Not {
+                                Err(())?
+                            }
+                            (output.to_string(), (l.to_string(), gate, r.to_string()))
+                        }
+                        _ => Err(())?,
+                    })
+                })
+                .collect::<Result<HashMap<_, _>, _>>()?;
+
+            let max_bit = |names: &mut dyn Iterator<Item = &String>| {
+                names
+                    .filter_map(|name| name.get(1..)?.parse::<usize>().ok())
+                    .max()
+                    .map_or(0, |bit| bit + 1)
+            };
+
+            Ok(Device {
+                bit_count: max_bit(&mut initial.keys()),
+                output_bits: max_bit(&mut connections.keys().filter(|x| x.starts_with('z'))),
+                wires: initial.clone(),
+                initial,
+                connections,
             })
-            .collect();
+        }
+    }
 
-        let max_bit = wires
-            .keys()
-            .filter_map(|name| name[1..].parse::<usize>().ok())
-            .max()
-            .unwrap()
-            + 1;
-
-        Device {
-            bit_count: max_bit,
-            wires,
-            connections,
+    impl Display for Device {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let mut initial = self.initial.iter().collect::<Vec<_>>();
+            initial.sort();
+            for (wire, value) in initial {
+                writeln!(f, "{}: {}", wire, *value as u8)?;
+            }
+
+            writeln!(f)?;
+
+            let mut connections = self.connections.iter().collect::<Vec<_>>();
+            connections.sort_by_key(|(output, _)| *output);
+            for (output, (l, gate, r)) in connections {
+                if *gate == LogicGate::Not {
+                    writeln!(f, "{} {} -> {}", gate, l, output)?;
+                } else {
+                    writeln!(f, "{} {} {} -> {}", l, gate, r, output)?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn data(path: &str) -> Device {
+        String::from_utf8(read_all(path)).unwrap().parse().unwrap()
+    }
+
+    // Build circuits programmatically, with auto named middle wires
+    struct DeviceBuilder {
+        bit_count: usize,
+        connections: HashMap<String, (String, LogicGate, String)>,
+        next_id: usize,
+    }
+
+    impl DeviceBuilder {
+        fn new(bit_count: usize) -> Self {
+            Self {
+                bit_count,
+                connections: HashMap::new(),
+                next_id: 0,
+            }
+        }
+
+        fn gate(&mut self, lhs: &str, gate: LogicGate, rhs: &str) -> String {
+            let output = format!("w{:03}", self.next_id);
+            self.next_id += 1;
+            self.connections
+                .insert(output.clone(), (lhs.to_string(), gate, rhs.to_string()));
+            output
+        }
+
+        fn not(&mut self, input: &str) -> String {
+            self.gate(input, LogicGate::Not, input)
+        }
+
+        fn half_adder(&mut self, a: &str, b: &str) -> (String, String) {
+            let sum = self.gate(a, LogicGate::Xor, b);
+            let carry = self.gate(a, LogicGate::And, b);
+            (sum, carry)
+        }
+
+        fn full_adder(&mut self, a: &str, b: &str, c: &str) -> (String, String) {
+            let (partial, carry1) = self.half_adder(a, b);
+            let (sum, carry2) = self.half_adder(&partial, c);
+            let carry = self.gate(&carry1, LogicGate::Or, &carry2);
+            (sum, carry)
+        }
+
+        fn zero(&mut self) -> String {
+            self.gate("x00", LogicGate::Xor, "x00")
+        }
+
+        // Rename wire to zxx if nobody else reads it, or add a buffer gate
+        fn output(&mut self, bit: usize, wire: &str) {
+            let name = format!("z{:02}", bit);
+            let used = self
+                .connections
+                .values()
+                .any(|(l, _, r)| l == wire || r == wire);
+            match self.connections.remove(wire) {
+                Some(connection) if !used => {
+                    self.connections.insert(name, connection);
+                }
+                connection => {
+                    if let Some(connection) = connection {
+                        self.connections.insert(wire.to_string(), connection);
+                    }
+                    self.connections
+                        .insert(name, (wire.to_string(), LogicGate::And, wire.to_string()));
+                }
+            }
+        }
+
+        fn build(self, output_bits: usize) -> Device {
+            let initial: HashMap<_, _> = ["x", "y"]
+                .into_iter()
+                .flat_map(|prefix| {
+                    (0..self.bit_count).map(move |bit| format!("{}{:02}", prefix, bit))
+                })
+                .map(|name| (name, false))
+                .collect();
+
+            Device {
+                bit_count: self.bit_count,
+                output_bits,
+                wires: initial.clone(),
+                initial,
+                connections: self.connections,
+            }
+        }
+
+        fn ripple_carry_adder(bit_count: usize) -> Device {
+            let mut builder = Self::new(bit_count);
+            let mut carry: Option<String> = None;
+            for bit in 0..bit_count {
+                let x = format!("x{:02}", bit);
+                let y = format!("y{:02}", bit);
+                let (sum, next) = match carry {
+                    Some(c) => builder.full_adder(&x, &y, &c),
+                    None => builder.half_adder(&x, &y),
+                };
+                builder.output(bit, &sum);
+                carry = Some(next);
+            }
+            if let Some(c) = carry {
+                builder.output(bit_count, &c);
+            }
+            builder.build(bit_count + 1)
+        }
+
+        fn array_multiplier(bit_count: usize) -> Device {
+            let mut builder = Self::new(bit_count);
+            let width = bit_count * 2;
+            let mut acc: Vec<Option<String>> = vec![None; width];
+
+            for i in 0..bit_count {
+                let y = format!("y{:02}", i);
+                let row = (0..bit_count)
+                    .map(|j| builder.gate(&format!("x{:02}", j), LogicGate::And, &y))
+                    .collect::<Vec<_>>();
+
+                let mut carry: Option<String> = None;
+                for (pos, slot) in acc.iter_mut().enumerate().skip(i) {
+                    let terms = [slot.take(), row.get(pos - i).cloned(), carry.take()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>();
+                    let (sum, next) = match &terms[..] {
+                        [] => (None, None),
+                        [a] => (Some(a.clone()), None),
+                        [a, b] => {
+                            let (s, c) = builder.half_adder(a, b);
+                            (Some(s), Some(c))
+                        }
+                        [a, b, c] => {
+                            let (s, c) = builder.full_adder(a, b, c);
+                            (Some(s), Some(c))
+                        }
+                        _ => unreachable!(),
+                    };
+                    *slot = sum;
+                    carry = next;
+                }
+            }
+
+            for (bit, wire) in acc.into_iter().enumerate() {
+                let wire = wire.unwrap_or_else(|| builder.zero());
+                builder.output(bit, &wire);
+            }
+            builder.build(width)
         }
     }
 
@@ -334,4 +556,112 @@ mod tests {
             .collect::<String>();
         assert_eq!(answer, "cbd,gmh,jmq,qrh,rqf,z06,z13,z38")
     }
+
+    const EXAMPLE: &str = "x00: 1
+x01: 0
+x02: 1
+x03: 1
+x04: 0
+y00: 1
+y01: 1
+y02: 1
+y03: 1
+y04: 1
+
+ntg XOR fgs -> mjb
+y02 OR x01 -> tnw
+kwq OR kpj -> z05
+x00 OR x03 -> fst
+tgd XOR rvg -> z01
+vdt OR tnw -> bfw
+bfw AND frj -> z10
+ffh OR nrd -> bqk
+y00 AND y03 -> djm
+y03 OR y00 -> psh
+bqk OR frj -> z08
+tnw OR fst -> frj
+gnj AND tgd -> z11
+bfw XOR mjb -> z00
+x03 OR x00 -> vdt
+gnj AND wpb -> z02
+x04 AND y00 -> kjc
+djm OR pbm -> qhw
+nrd AND vdt -> hwm
+kjc AND fst -> rvg
+y04 OR y02 -> fgs
+y01 AND x02 -> pbm
+ntg OR kjc -> kwq
+psh XOR fgs -> tgd
+qhw XOR tgd -> z09
+pbm OR djm -> kpj
+x03 XOR y03 -> ffh
+x00 XOR y04 -> ntg
+bfw OR bqk -> z06
+nrd XOR fgs -> wpb
+frj XOR qhw -> z04
+bqk OR frj -> z07
+y03 OR x01 -> nrd
+hwm AND bqk -> z03
+tgd XOR rvg -> z12
+tnw OR pbm -> gnj
+";
+
+    #[test]
+    fn example_round_trip() {
+        let mut device: Device = EXAMPLE.parse().unwrap();
+        assert_eq!(device.output_number().unwrap(), 2024);
+
+        let text = device.to_string();
+        let mut parsed: Device = text.parse().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.output_number().unwrap(), 2024);
+
+        // Malformed values are errors, odd wire names are just not counted as bits
+        assert!("x00: 2\n\nx00 AND x00 -> z00".parse::<Device>().is_err());
+        assert!("x00: x\n\nx00 AND x00 -> z00".parse::<Device>().is_err());
+        let device: Device = ": 1\nä0: 0\n\nä0 AND ä0 -> z00".parse().unwrap();
+        assert_eq!(device.bit_count, 0);
+        assert_eq!(device.output_bits, 1);
+    }
+
+    #[test]
+    fn extended_gates() {
+        let mut device: Device = "a: 1\nb: 0\n\na NAND b -> z00\na NOR b -> z01\na XNOR b -> z02\nNOT a -> z03\nNOT b -> z04\n"
+            .parse()
+            .unwrap();
+        assert_eq!(device.output().unwrap(), [true, false, false, false, true]);
+        assert_eq!(
+            device.to_string().parse::<Device>().unwrap().to_string(),
+            device.to_string()
+        );
+        assert!("a: 1\n\na NOT a -> z00\n".parse::<Device>().is_err());
+
+        let mut builder = DeviceBuilder::new(1);
+        let nx = builder.not("x00");
+        builder.output(0, &nx);
+        let mut device = builder.build(1);
+        assert_eq!(device.error_bits(0, 0, 1).unwrap(), []);
+        assert_eq!(device.error_bits(1, 0, 0).unwrap(), []);
+    }
+
+    #[test]
+    fn built_adder_and_multiplier() {
+        let mut adder = DeviceBuilder::ripple_carry_adder(8);
+        let mut multiplier = DeviceBuilder::array_multiplier(6);
+        for (x, y) in [(0, 0), (1, 255), (200, 100), (255, 255), (37, 91)] {
+            assert_eq!(adder.error_bits(x, y, x + y).unwrap(), []);
+            let (x, y) = (x % 64, y % 64);
+            assert_eq!(multiplier.error_bits(x, y, x * y).unwrap(), []);
+        }
+        assert!(check(&mut adder, 0..8, false).is_empty());
+
+        let mut parsed: Device = adder.to_string().parse().unwrap();
+        assert_eq!(parsed.to_string(), adder.to_string());
+        assert_eq!(parsed.error_bits(200, 100, 300).unwrap(), []);
+
+        adder.swap("z03", "z05");
+        let mut swapped: Device = adder.to_string().parse().unwrap();
+        let errors = check(&mut swapped, 0..8, false);
+        assert!(errors.contains_key(&3) && errors.contains_key(&5));
+    }
 }
@@ -1,83 +1,320 @@
 #[cfg(test)]
 mod test {
+    use std::ops::Range;
+
     use advent2024::*;
 
-    fn data() -> impl Iterator<Item = u8> {
-        read_by_byte("tests/data/day3.input.txt")
+    fn data() -> Vec<u8> {
+        read_all("tests/data/day3.input.txt")
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Instruction {
+        name: &'static str,
+        arity: usize,
+        // Max digits of every argument
+        digits: usize,
+    }
+
+    const MUL: Instruction = Instruction {
+        name: "mul",
+        arity: 2,
+        digits: 3,
+    };
+    const DO: Instruction = Instruction {
+        name: "do",
+        arity: 0,
+        digits: 0,
+    };
+    const DONT: Instruction = Instruction {
+        name: "don't",
+        arity: 0,
+        digits: 0,
+    };
+
+    const GRAMMAR: &[Instruction] = &[MUL, DO, DONT];
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Token {
+        Mul(u32, u32),
+        Do,
+        Dont,
+        Other { name: &'static str, args: Vec<u32> },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Spanned {
+        token: Token,
+        span: Range<usize>,
+    }
+
+    // An instruction name followed by something wrong, `span` includes the wrong byte if any
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct NearMiss {
+        span: Range<usize>,
+        expected: &'static str,
+    }
+
+    struct Lexer<'a> {
+        input: &'a [u8],
+        grammar: &'a [Instruction],
+        pos: usize,
     }
 
-    fn parse(switch: bool) -> u32 {
-        enum State {
-            Init,
-            M,
-            U,
-            L,
-            LeftParen,
-            Num1(String),
-            Comma(u32),
-            Num2(u32, String),
+    impl<'a> Lexer<'a> {
+        fn new(input: &'a [u8], grammar: &'a [Instruction]) -> Self {
+            Self {
+                input,
+                grammar,
+                pos: 0,
+            }
         }
 
-        let mut result = 0;
-        let mut enable = true;
-        let mut queue = std::collections::VecDeque::with_capacity(8);
-        let mut state = State::Init;
-        for c in data() {
-            if switch {
-                while queue.len() >= 7 {
-                    queue.pop_front();
+        // Returns the end of token or where it goes wrong
+        fn instruction(&self, ins: &Instruction) -> Result<(Token, usize), NearMiss> {
+            let start = self.pos;
+            let mut pos = start + ins.name.len();
+            let miss = |pos: usize, expected| NearMiss {
+                span: start..(pos + 1).min(self.input.len()),
+                expected,
+            };
+            let expect = |pos: usize, c: u8, expected| {
+                (self.input.get(pos) == Some(&c))
+                    .then_some(pos + 1)
+                    .ok_or_else(|| miss(pos, expected))
+            };
+
+            pos = expect(pos, b'(', "(")?;
+            let mut args = Vec::with_capacity(ins.arity);
+            for i in 0..ins.arity {
+                if i > 0 {
+                    pos = expect(pos, b',', ",")?;
                 }
-                queue.push_back(c);
-                if c == b')' {
-                    let s = queue.make_contiguous();
-                    if matches!(s.last_chunk(), Some([b'd', b'o', b'(', b')'])) {
-                        enable = true;
-                    }
-                    if matches!(
-                        s.last_chunk(),
-                        Some([b'd', b'o', b'n', b'\'', b't', b'(', b')'])
-                    ) {
-                        enable = false;
-                    }
+                let len = self.input[pos..]
+                    .iter()
+                    .take(ins.digits + 1)
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                if len == 0 || len > ins.digits {
+                    return Err(miss(pos + len.min(ins.digits), "digit"));
                 }
+                let digits = std::str::from_utf8(&self.input[pos..pos + len]).unwrap();
+                // Grammar may allow more digits than an argument holds
+                let Ok(arg) = digits.parse() else {
+                    return Err(miss(pos + len - 1, "smaller number"));
+                };
+                args.push(arg);
+                pos += len;
             }
-            state = match (state, c) {
-                (_, b'm') => State::M,
-                (State::M, b'u') => State::U,
-                (State::U, b'l') => State::L,
-                (State::L, b'(') => State::LeftParen,
-                (State::LeftParen, b'0'..=b'9') => State::Num1(String::from(c as char)),
-                (State::Num1(mut s), b'0'..=b'9') if s.len() < 3 => {
-                    s.push(c as char);
-                    State::Num1(s)
-                }
-                (State::Num1(s), b',') => State::Comma(s.parse().unwrap()),
-                (State::Comma(num1), b'0'..=b'9') => State::Num2(num1, String::from(c as char)),
-                (State::Num2(num1, mut s), b'0'..=b'9') if s.len() < 3 => {
-                    s.push(c as char);
-                    State::Num2(num1, s)
-                }
-                (State::Num2(num1, s), b')') => {
-                    let num2: u32 = s.parse().unwrap();
-                    if enable {
-                        result += num1 * num2;
+            pos = expect(pos, b')', ")")?;
+
+            let token = match (ins.name, args.as_slice()) {
+                ("mul", [a, b]) => Token::Mul(*a, *b),
+                ("do", []) => Token::Do,
+                ("don't", []) => Token::Dont,
+                (name, _) => Token::Other { name, args },
+            };
+            Ok((token, pos))
+        }
+    }
+
+    impl Iterator for Lexer<'_> {
+        type Item = Result<Spanned, NearMiss>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while self.pos < self.input.len() {
+                let start = self.pos;
+                let mut miss = None;
+                for ins in self.grammar {
+                    if !self.input[start..].starts_with(ins.name.as_bytes()) {
+                        continue;
+                    }
+                    match self.instruction(ins) {
+                        Ok((token, end)) => {
+                            self.pos = end;
+                            return Some(Ok(Spanned {
+                                token,
+                                span: start..end,
+                            }));
+                        }
+                        // Keep the one which goes furthest
+                        Err(m) => {
+                            if miss
+                                .as_ref()
+                                .is_none_or(|old: &NearMiss| m.span.end > old.span.end)
+                            {
+                                miss = Some(m);
+                            }
+                        }
                     }
-                    State::Init
                 }
-                _ => State::Init,
-            };
+                // A failed token may contain the start of another one
+                self.pos += 1;
+                if let Some(miss) = miss {
+                    return Some(Err(miss));
+                }
+            }
+            None
         }
+    }
 
-        result
+    fn run(input: &[u8], switch: bool) -> u32 {
+        let mut enable = true;
+        Lexer::new(input, GRAMMAR)
+            .filter_map(Result::ok)
+            .map(|Spanned { token, .. }| match token {
+                Token::Mul(a, b) if enable => a * b,
+                Token::Do if switch => {
+                    enable = true;
+                    0
+                }
+                Token::Dont if switch => {
+                    enable = false;
+                    0
+                }
+                _ => 0,
+            })
+            .sum()
     }
 
     #[test]
     fn part1() {
-        assert_eq!(parse(false), 183380722);
+        assert_eq!(run(&data(), false), 183380722);
     }
 
     #[test]
     fn part2() {
-        assert_eq!(parse(true), 82733683);
+        assert_eq!(run(&data(), true), 82733683);
+    }
+
+    const EXAMPLE1: &str =
+        "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const EXAMPLE2: &str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn example_tokens() {
+        assert_eq!(run(EXAMPLE1.as_bytes(), false), 161);
+        assert_eq!(run(EXAMPLE2.as_bytes(), true), 48);
+
+        let (tokens, misses): (Vec<_>, Vec<_>) =
+            Lexer::new(EXAMPLE2.as_bytes(), GRAMMAR).partition(Result::is_ok);
+        let tokens = tokens.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            tokens.iter().map(|t| t.token.clone()).collect::<Vec<_>>(),
+            [
+                Token::Mul(2, 4),
+                Token::Dont,
+                Token::Mul(5, 5),
+                Token::Mul(11, 8),
+                Token::Do,
+                Token::Mul(8, 5),
+            ]
+        );
+        assert_eq!(tokens[0].span, 1..9);
+        assert_eq!(&EXAMPLE2[tokens[1].span.clone()], "don't()");
+
+        let misses = misses
+            .into_iter()
+            .map(|m| {
+                let m = m.unwrap_err();
+                (&EXAMPLE2[m.span], m.expected)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(misses, [("mul[", "("), ("mul(32,64]", ")")]);
+    }
+
+    #[test]
+    fn custom_instructions() {
+        let grammar = [
+            MUL,
+            Instruction {
+                name: "add",
+                arity: 3,
+                digits: 2,
+            },
+            Instruction {
+                name: "neg",
+                arity: 1,
+                digits: 5,
+            },
+            DO,
+        ];
+        let input = "add(1,22,3)neg(12345)mul(4*add(1,2)add(100,1,1)neg(123456)do_mul(1234,1)";
+        let result = Lexer::new(input.as_bytes(), &grammar).collect::<Vec<_>>();
+        let tokens = result
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|t| (t.token.clone(), &input[t.span.clone()]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [
+                (
+                    Token::Other {
+                        name: "add",
+                        args: vec![1, 22, 3]
+                    },
+                    "add(1,22,3)"
+                ),
+                (
+                    Token::Other {
+                        name: "neg",
+                        args: vec![12345]
+                    },
+                    "neg(12345)"
+                ),
+            ]
+        );
+
+        let misses = result
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .map(|m| (&input[m.span.clone()], m.expected))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            misses,
+            [
+                ("mul(4*", ","),
+                ("add(1,2)", ","),
+                ("add(100", "digit"),
+                ("neg(123456", "digit"),
+                ("do_", "("),
+                ("mul(1234", "digit"),
+            ]
+        );
+
+        // The longest near miss is reported, and too big numbers are misses
+        let grammar = [
+            DO,
+            DONT,
+            Instruction {
+                name: "big",
+                arity: 1,
+                digits: 12,
+            },
+        ];
+        let input = "don't_big(99999999999)big(4294967295)";
+        let result = Lexer::new(input.as_bytes(), &grammar).collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            [
+                Err(NearMiss {
+                    span: 0..6,
+                    expected: "(",
+                }),
+                Err(NearMiss {
+                    span: 6..21,
+                    expected: "smaller number",
+                }),
+                Ok(Spanned {
+                    token: Token::Other {
+                        name: "big",
+                        args: vec![u32::MAX],
+                    },
+                    span: 22..37,
+                }),
+            ]
+        );
     }
 }
@@ -1,13 +1,19 @@
 #[cfg(test)]
 mod test {
+    use std::collections::{HashMap, HashSet, VecDeque};
+
     use advent2024::*;
 
-    fn data() -> Vec<Vec<char>> {
-        read_by_line("tests/data/day4.input.txt")
+    fn parse(lines: impl Iterator<Item = String>) -> Vec<Vec<char>> {
+        lines
             .map(|s| s.chars().filter(|c| !matches!(c, '\r' | '\n')).collect())
             .collect()
     }
 
+    fn data() -> Vec<Vec<char>> {
+        parse(read_by_line("tests/data/day4.input.txt"))
+    }
+
     /// Get an iterator that generates all position in the input, in format of (row, col)
     fn pos(input: &[Vec<char>]) -> impl Iterator<Item = (usize, usize)> + '_ {
         input
@@ -16,142 +22,331 @@ mod test {
             .flat_map(|(i, line)| line.iter().enumerate().map(move |(j, _)| (i, j)))
     }
 
-    #[test]
-    fn part1() {
-        /// An iterator that walk from `index` position follow the `direction`
-        struct Walker<'a> {
-            input: &'a [Vec<char>],
-            index: (isize, isize),
-            direction: (isize, isize),
+    fn get(input: &[Vec<char>], (row, col): (isize, isize)) -> Option<char> {
+        if row < 0 || col < 0 {
+            return None;
         }
+        input.get(row as usize)?.get(col as usize).copied()
+    }
 
-        impl<'a> Walker<'a> {
-            pub fn new(input: &'a [Vec<char>], index: (usize, usize)) -> Self {
-                Self {
-                    input,
-                    index: (index.0 as isize, index.1 as isize),
-                    direction: (0, 0),
+    static DIRECTIONS: [(isize, isize); 8] = [
+        (0, 1),
+        (1, 1),
+        (1, 0),
+        (1, -1),
+        (0, -1),
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+    ];
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct WordMatch {
+        word: usize,
+        start: (usize, usize),
+        direction: (isize, isize),
+    }
+
+    /// Aho-Corasick automaton, finds all words in one walk of a line
+    struct WordSearch {
+        words: Vec<Vec<char>>,
+        goto: Vec<HashMap<char, usize>>,
+        fail: Vec<usize>,
+        // Index of words which end at the state, include ones from fail links
+        output: Vec<Vec<usize>>,
+    }
+
+    impl WordSearch {
+        pub fn new(words: &[&str]) -> Self {
+            assert!(words.iter().all(|w| !w.is_empty()), "empty word");
+            let words: Vec<Vec<char>> = words.iter().map(|w| w.chars().collect()).collect();
+            let mut goto = vec![HashMap::new()];
+            let mut output = vec![vec![]];
+            for (idx, word) in words.iter().enumerate() {
+                let mut state = 0;
+                for c in word {
+                    state = match goto[state].get(c) {
+                        Some(next) => *next,
+                        None => {
+                            goto.push(HashMap::new());
+                            output.push(vec![]);
+                            let next = goto.len() - 1;
+                            goto[state].insert(*c, next);
+                            next
+                        }
+                    };
                 }
+                output[state].push(idx);
             }
 
-            pub fn direction(mut self, direction: (isize, isize)) -> Self {
-                self.direction = direction;
-                self
+            let mut fail = vec![0; goto.len()];
+            let mut q: VecDeque<usize> = goto[0].values().copied().collect();
+            while let Some(state) = q.pop_front() {
+                let edges = goto[state]
+                    .iter()
+                    .map(|(c, n)| (*c, *n))
+                    .collect::<Vec<_>>();
+                for (c, next) in edges {
+                    let mut f = fail[state];
+                    while f != 0 && !goto[f].contains_key(&c) {
+                        f = fail[f];
+                    }
+                    fail[next] = goto[f].get(&c).copied().filter(|x| *x != next).unwrap_or(0);
+                    let inherited = output[fail[next]].clone();
+                    output[next].extend(inherited);
+                    q.push_back(next);
+                }
             }
 
-            pub fn chars(&mut self) -> [Option<char>; 4] {
-                std::array::from_fn(|_| self.next())
+            Self {
+                words,
+                goto,
+                fail,
+                output,
             }
         }
 
-        impl Iterator for Walker<'_> {
-            type Item = char;
+        fn step(&self, mut state: usize, c: char) -> usize {
+            loop {
+                if let Some(next) = self.goto[state].get(&c) {
+                    return *next;
+                }
+                if state == 0 {
+                    return 0;
+                }
+                state = self.fail[state];
+            }
+        }
 
-            fn next(&mut self) -> Option<Self::Item> {
-                let (line, col) = self.index;
-                if line < 0 || col < 0 {
-                    return None;
+        // Walk a line from `start` to the `direction` until out of the grid
+        fn search_line(
+            &self,
+            input: &[Vec<char>],
+            start: (isize, isize),
+            direction: (isize, isize),
+            result: &mut Vec<WordMatch>,
+        ) {
+            let (mut pos, mut state) = (start, 0);
+            while let Some(c) = get(input, pos) {
+                state = self.step(state, c);
+                for word in &self.output[state] {
+                    let back = self.words[*word].len() as isize - 1;
+                    // One letter words are the same in all directions, only report the first
+                    if back == 0 && direction != DIRECTIONS[0] {
+                        continue;
+                    }
+                    result.push(WordMatch {
+                        word: *word,
+                        start: (
+                            (pos.0 - back * direction.0) as usize,
+                            (pos.1 - back * direction.1) as usize,
+                        ),
+                        direction,
+                    });
                 }
+                pos = (pos.0 + direction.0, pos.1 + direction.1);
+            }
+        }
 
-                let c = self.input.get(line as usize)?.get(col as usize)?;
+        /// Matches of all words in all 8 directions
+        pub fn find(&self, input: &[Vec<char>]) -> Vec<WordMatch> {
+            let mut result = vec![];
+            for direction in DIRECTIONS {
+                // Lines start from cells whose previous cell is out of the grid
+                for (row, col) in pos(input) {
+                    let start = (row as isize, col as isize);
+                    let prev = (start.0 - direction.0, start.1 - direction.1);
+                    if get(input, prev).is_none() {
+                        self.search_line(input, start, direction, &mut result);
+                    }
+                }
+            }
+            result
+        }
+    }
 
-                let (y, x) = self.direction;
-                self.index = (line + y, col + x);
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Orientation {
+        // Clockwise quarter turns, after the flip
+        rotation: u8,
+        // Mirror left and right
+        flipped: bool,
+    }
 
-                Some(*c)
-            }
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct TemplateMatch {
+        top_left: (usize, usize),
+        orientation: Orientation,
+    }
+
+    /// A 2D shape, `None` cell matches anything
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Template(Vec<Vec<Option<char>>>);
+
+    impl Template {
+        pub fn parse(s: &str, wildcard: char) -> Self {
+            Self(
+                s.lines()
+                    .map(|line| line.chars().map(|c| (c != wildcard).then_some(c)).collect())
+                    .collect(),
+            )
         }
 
-        fn check(chars: &[Option<char>; 4]) -> bool {
-            matches!(
-                chars,
-                [Some('X'), Some('M'), Some('A'), Some('S')]
-                    | [Some('S'), Some('A'), Some('M'), Some('X')]
+        fn rotate(&self) -> Self {
+            let (height, width) = (self.0.len(), self.0.first().map_or(0, Vec::len));
+            Self(
+                (0..width)
+                    .map(|i| (0..height).map(|j| self.0[height - 1 - j][i]).collect())
+                    .collect(),
             )
         }
 
-        fn check_position(input: &[Vec<char>], position: (usize, usize)) -> usize {
-            [(0, 1), (1, 0), (1, 1), (1, -1)]
-                .into_iter()
-                .map(|dir| Walker::new(input, position).direction(dir).chars())
-                .filter(check)
-                .count()
+        fn flip(&self) -> Self {
+            Self(
+                self.0
+                    .iter()
+                    .map(|line| line.iter().rev().copied().collect())
+                    .collect(),
+            )
         }
 
+        /// All distinct rotations and reflections
+        pub fn orientations(&self) -> Vec<(Orientation, Template)> {
+            let mut seen = HashSet::new();
+            let mut result = vec![];
+            for flipped in [false, true] {
+                let mut t = if flipped { self.flip() } else { self.clone() };
+                for rotation in 0..4 {
+                    if seen.insert(t.clone()) {
+                        result.push((Orientation { rotation, flipped }, t.clone()));
+                    }
+                    t = t.rotate();
+                }
+            }
+            result
+        }
+
+        fn matches_at(&self, input: &[Vec<char>], (row, col): (usize, usize)) -> bool {
+            self.0.iter().enumerate().all(|(i, line)| {
+                line.iter().enumerate().all(|(j, c)| {
+                    let got = get(input, ((row + i) as isize, (col + j) as isize));
+                    got.is_some() && (c.is_none() || *c == got)
+                })
+            })
+        }
+
+        pub fn find(&self, input: &[Vec<char>]) -> Vec<TemplateMatch> {
+            let orientations = self.orientations();
+            pos(input)
+                .flat_map(|top_left| {
+                    orientations
+                        .iter()
+                        .filter(move |(_, t)| t.matches_at(input, top_left))
+                        .map(move |(orientation, _)| TemplateMatch {
+                            top_left,
+                            orientation: *orientation,
+                        })
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn part1() {
         let input = data();
-        let result = pos(&input)
-            .map(|pos| check_position(&input, pos))
-            .sum::<usize>();
+        let result = WordSearch::new(&["XMAS"]).find(&input).len();
 
         assert_eq!(result, 2358);
     }
 
     #[test]
     fn part2() {
-        struct CrossWalker<'a> {
-            input: &'a [Vec<char>],
-            index: (isize, isize),
-            step: usize,
-        }
-
-        impl<'a> CrossWalker<'a> {
-            pub fn new(input: &'a [Vec<char>], index: (usize, usize)) -> Self {
-                Self {
-                    input,
-                    index: (index.0 as isize, index.1 as isize),
-                    step: 0,
-                }
-            }
-
-            pub fn chars(&mut self) -> [Option<char>; 5] {
-                std::array::from_fn(|_| self.next())
-            }
-        }
-
-        impl Iterator for CrossWalker<'_> {
-            type Item = char;
+        let input = data();
+        let result = Template::parse("M.S\n.A.\nM.S", '.').find(&input).len();
 
-            fn next(&mut self) -> Option<Self::Item> {
-                static STEP_OFFSET: [(isize, isize); 5] =
-                    [(0, 0), (-1, -1), (1, 1), (1, -1), (-1, 1)];
+        assert_eq!(result, 1737);
+    }
 
-                if self.step > 4 {
-                    return None;
-                }
+    const EXAMPLE: &str = "MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX";
 
-                let (oy, ox) = STEP_OFFSET[self.step];
-                let (mut line, mut col) = self.index;
-                line += oy;
-                col += ox;
-                self.step += 1;
+    fn example(s: &str) -> Vec<Vec<char>> {
+        parse(s.lines().map(str::to_string))
+    }
 
-                if line < 0 || col < 0 {
-                    return None;
-                }
+    #[test]
+    fn example_search() {
+        let input = example(EXAMPLE);
+        let matches = WordSearch::new(&["XMAS"]).find(&input);
+        assert_eq!(matches.len(), 18);
+        assert!(matches.contains(&WordMatch {
+            word: 0,
+            start: (4, 6),
+            direction: (0, -1),
+        }));
 
-                Some(*self.input.get(line as usize)?.get(col as usize)?)
-            }
-        }
+        let xmas = Template::parse("M.S\n.A.\nM.S", '.');
+        assert_eq!(xmas.orientations().len(), 4);
+        let matches = xmas.find(&input);
+        assert_eq!(matches.len(), 9);
+        assert!(matches.contains(&TemplateMatch {
+            top_left: (0, 1),
+            orientation: Orientation {
+                rotation: 0,
+                flipped: false,
+            },
+        }));
+    }
 
-        fn check(chars: &[Option<char>; 5]) -> bool {
-            matches!(chars[0], Some('A'))
-                && matches!(
-                    &chars[1..=2],
-                    [Some('M'), Some('S')] | [Some('S'), Some('M')]
-                )
-                && matches!(
-                    &chars[3..=4],
-                    [Some('M'), Some('S')] | [Some('S'), Some('M')]
-                )
-        }
+    #[test]
+    fn many_words() {
+        let input = example("CAT\nXAX\nTAC");
+        let matches = WordSearch::new(&["CAT", "AT", "X"]).find(&input);
+        let count = |word| matches.iter().filter(|m| m.word == word).count();
+        assert_eq!(count(0), 2);
+        assert_eq!(count(1), 4);
+        // One letter words are found once
+        assert_eq!(count(2), 2);
+        assert!(matches.contains(&WordMatch {
+            word: 2,
+            start: (1, 0),
+            direction: DIRECTIONS[0],
+        }));
+        assert!(matches.contains(&WordMatch {
+            word: 0,
+            start: (2, 2),
+            direction: (0, -1),
+        }));
+        assert!(matches.contains(&WordMatch {
+            word: 1,
+            start: (1, 1),
+            direction: (-1, 1),
+        }));
 
-        let input = data();
+        let matches = Template::parse("AB", '.').find(&example("AB\nBA"));
+        let mut found = matches
+            .iter()
+            .map(|m| (m.top_left, m.orientation.rotation))
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, [((0, 0), 0), ((0, 0), 1), ((0, 1), 3), ((1, 0), 2)]);
+        assert!(matches.iter().all(|m| !m.orientation.flipped));
 
-        let result = pos(&input)
-            .map(|position| CrossWalker::new(&input, position).chars())
-            .filter(check)
-            .count();
+        let empty = Template::parse("", '.');
+        assert_eq!(empty.rotate(), empty);
+    }
 
-        assert_eq!(result, 1737);
+    #[test]
+    #[should_panic(expected = "empty word")]
+    fn empty_word() {
+        WordSearch::new(&["XMAS", ""]);
     }
 }
@@ -1,35 +1,137 @@
 #[cfg(test)]
 mod tests {
-    use std::{
-        cmp::Ordering,
-        collections::{HashMap, HashSet},
-    };
+    use std::collections::{HashMap, HashSet};
 
     use advent2024::*;
 
+    // Page -> pages which must be printed after it
     #[derive(Debug, Default)]
     struct Rule(HashMap<u32, HashSet<u32>>);
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Order {
+        pages: Vec<u32>,
+        // Only one valid order exists
+        unique: bool,
+    }
+
     impl Rule {
-        fn check(&self, before: &u32, after: &u32) -> bool {
-            if let Some(afters) = self.0.get(after) {
-                !afters.contains(before)
-            } else {
-                true
-            }
+        fn must_before(&self, before: &u32, after: &u32) -> bool {
+            self.0
+                .get(before)
+                .is_some_and(|afters| afters.contains(after))
+        }
+
+        fn afters<'a>(
+            &'a self,
+            page: &u32,
+            pages: &'a HashSet<u32>,
+        ) -> impl Iterator<Item = u32> + 'a {
+            self.0
+                .get(page)
+                .into_iter()
+                .flatten()
+                .filter(|p| pages.contains(p))
+                .copied()
         }
 
-        fn cmp(&self, a: &u32, b: &u32) -> Ordering {
-            if a == b {
-                return Ordering::Equal;
+        // Rules `before|after` broken by the update
+        fn violations(&self, update: &[u32]) -> Vec<(u32, u32)> {
+            update
+                .iter()
+                .enumerate()
+                .flat_map(|(i, a)| update[i + 1..].iter().map(move |b| (*b, *a)))
+                .filter(|(before, after)| self.must_before(before, after))
+                .collect()
+        }
+
+        // Topological sort which only uses rules between pages of the update, ties keep the
+        // order in update. A page printed more than once is kept once, at its first position.
+        // Returns a cycle if there is no valid order.
+        fn order(&self, update: &[u32]) -> Result<Order, Vec<u32>> {
+            let mut pages: HashSet<u32> = HashSet::new();
+            let update: Vec<u32> = update
+                .iter()
+                .copied()
+                .filter(|p| pages.insert(*p))
+                .collect();
+            let mut in_degree: HashMap<u32, usize> = update.iter().map(|p| (*p, 0)).collect();
+            for page in &pages {
+                for after in self.afters(page, &pages) {
+                    *in_degree.get_mut(&after).unwrap() += 1;
+                }
+            }
+
+            let mut ready: Vec<u32> = update
+                .iter()
+                .copied()
+                .filter(|p| in_degree[p] == 0)
+                .collect();
+            let mut result = Vec::with_capacity(update.len());
+            let mut unique = true;
+            while !ready.is_empty() {
+                unique &= ready.len() == 1;
+                let page = ready.remove(0);
+                result.push(page);
+                for after in self.afters(&page, &pages) {
+                    let d = in_degree.get_mut(&after).unwrap();
+                    *d -= 1;
+                    if *d == 0 {
+                        ready.push(after);
+                    }
+                }
+                let position = |p: &u32| update.iter().position(|x| x == p);
+                ready.sort_by_key(position);
+            }
+
+            if result.len() < pages.len() {
+                let rest = pages.into_iter().filter(|p| in_degree[p] > 0).collect();
+                return Err(self.cycle(&rest).unwrap());
             }
 
-            if let Some(afters) = self.0.get(b) {
-                if afters.contains(a) {
-                    return Ordering::Greater;
+            Ok(Order {
+                pages: result,
+                unique,
+            })
+        }
+
+        // Any cycle in rules between these pages, like `[a, b, c]` for `a|b`, `b|c` and `c|a`
+        fn cycle(&self, pages: &HashSet<u32>) -> Option<Vec<u32>> {
+            fn dfs(
+                rule: &Rule,
+                page: u32,
+                pages: &HashSet<u32>,
+                done: &mut HashSet<u32>,
+                path: &mut Vec<u32>,
+            ) -> Option<Vec<u32>> {
+                if let Some(idx) = path.iter().position(|p| *p == page) {
+                    return Some(path[idx..].to_vec());
                 }
+                if !done.insert(page) {
+                    return None;
+                }
+                path.push(page);
+                for after in rule.afters(&page, pages) {
+                    if let Some(cycle) = dfs(rule, after, pages, done, path) {
+                        return Some(cycle);
+                    }
+                }
+                path.pop();
+                None
             }
 
-            Ordering::Less
+            let mut starts = pages.iter().copied().collect::<Vec<_>>();
+            starts.sort();
+            let mut done = HashSet::new();
+            starts
+                .into_iter()
+                .find_map(|page| dfs(self, page, pages, &mut done, &mut vec![]))
+        }
+    }
+
+    impl Order {
+        fn middle(&self) -> u32 {
+            self.pages[self.pages.len() / 2]
         }
     }
 
@@ -43,12 +145,10 @@ mod tests {
         }
     }
 
-    fn data() -> (Rule, Vec<Vec<u32>>) {
-        let mut lines = read_by_line("tests/data/day5.input.txt");
-
+    fn parse(mut lines: impl Iterator<Item = String>) -> (Rule, Vec<Vec<u32>>) {
         let rule = lines
             .by_ref()
-            .take_while(|line| line != "\n")
+            .take_while(|line| !line.trim_end().is_empty())
             .map(|line| {
                 let mut parts = line.trim_end().splitn(2, '|');
                 (
@@ -70,13 +170,17 @@ mod tests {
         (rule, updates)
     }
 
+    fn data() -> (Rule, Vec<Vec<u32>>) {
+        parse(read_by_line("tests/data/day5.input.txt"))
+    }
+
     #[test]
     fn part1() {
         let (rule, updates) = data();
 
         let result = updates
             .into_iter()
-            .filter(|update| update.is_sorted_by(|a, b| rule.check(a, b)))
+            .filter(|update| rule.violations(update).is_empty())
             .map(|update| update[update.len() / 2])
             .sum::<u32>();
 
@@ -89,13 +193,99 @@ mod tests {
 
         let result = updates
             .into_iter()
-            .filter(|update| !update.is_sorted_by(|a, b| rule.check(a, b)))
-            .map(|mut update| {
-                update.sort_unstable_by(|a, b| rule.cmp(a, b));
-                update[update.len() / 2]
-            })
+            .filter(|update| !rule.violations(update).is_empty())
+            .map(|update| rule.order(&update).unwrap().middle())
             .sum::<u32>();
 
         assert_eq!(result, 6204);
     }
+
+    const EXAMPLE: &str = "47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,61,53,29,13
+75,29,13
+75,97,47,61,53
+61,13,29
+97,13,75,29,47";
+
+    fn example(s: &str) -> (Rule, Vec<Vec<u32>>) {
+        parse(s.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn example_order() {
+        let (rule, updates) = example(EXAMPLE);
+        let (valid, invalid): (Vec<_>, Vec<_>) = updates
+            .iter()
+            .partition(|update| rule.violations(update).is_empty());
+        assert_eq!(valid.iter().map(|u| u[u.len() / 2]).sum::<u32>(), 143);
+
+        let fixed = invalid
+            .iter()
+            .map(|update| rule.order(update).unwrap())
+            .collect::<Vec<_>>();
+        assert!(fixed.iter().all(|order| order.unique));
+        assert_eq!(fixed.iter().map(Order::middle).sum::<u32>(), 123);
+        assert_eq!(fixed[2].pages, [97, 75, 47, 29, 13]);
+
+        assert_eq!(rule.violations(&updates[3]), [(97, 75)]);
+        assert_eq!(rule.violations(&updates[4]), [(29, 13)]);
+        assert_eq!(
+            rule.violations(&updates[5]),
+            [(75, 13), (29, 13), (47, 13), (47, 29)]
+        );
+
+        let all = rule.0.keys().copied().chain([13]).collect();
+        assert_eq!(rule.cycle(&all), None);
+    }
+
+    #[test]
+    fn ambiguous_and_cycle() {
+        let (rule, _) = example("1|2\n1|3\n2|4\n4|5\n5|2\n\n1,2");
+        assert_eq!(
+            rule.order(&[3, 2, 1]),
+            Ok(Order {
+                pages: vec![1, 3, 2],
+                unique: false,
+            })
+        );
+        assert_eq!(
+            rule.order(&[2, 1]),
+            Ok(Order {
+                pages: vec![1, 2],
+                unique: true,
+            })
+        );
+        // Only rules between the pages of an update matter
+        assert!(rule.order(&[1, 2, 5]).is_ok());
+        assert_eq!(rule.order(&[5, 4, 2, 1]), Err(vec![2, 4, 5]));
+        assert_eq!(rule.violations(&[5, 2, 4]), [(4, 5)]);
+
+        // Duplicated pages are kept once
+        let order = rule.order(&[2, 3, 2, 1, 3]).unwrap();
+        assert_eq!(order.pages, [1, 2, 3]);
+        assert_eq!(order.middle(), 2);
+        assert_eq!(rule.order(&[2, 2]).unwrap().pages, [2]);
+    }
 }
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use advent2024::*;
 
     static DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
@@ -122,9 +124,154 @@ mod tests {
         }
     }
 
-    fn data() -> (Map, (usize, usize)) {
+    type Pos = (usize, usize);
+
+    // Where the guard stops (the cell before an obstruction) when walking from a cell in a
+    // direction, `None` means walking out of the map.
+    struct JumpTable {
+        height: usize,
+        width: usize,
+        jumps: Vec<Vec<[Option<Pos>; 4]>>,
+    }
+
+    // Turn points are recorded as the stop position and the direction before turning
+    type Turn = (Pos, usize);
+
+    #[derive(Debug)]
+    enum Patrol {
+        Out(Vec<Turn>),
+        Loop(Vec<Turn>),
+    }
+
+    impl Map {
+        fn next(&self, (row, col): Pos, dir: usize) -> Option<Pos> {
+            let (oy, ox) = DIRECTIONS[dir];
+            let row = row.checked_add_signed(oy).filter(|r| *r < self.0.len())?;
+            let col = col
+                .checked_add_signed(ox)
+                .filter(|c| *c < self.0[row].len())?;
+            Some((row, col))
+        }
+
+        pub fn jump_table(&self) -> JumpTable {
+            let (height, width) = (self.0.len(), self.0[0].len());
+            let mut jumps = vec![vec![[None; 4]; width]; height];
+            let rows = |rev| -> Box<dyn Iterator<Item = usize>> {
+                if rev {
+                    Box::new((0..height).rev())
+                } else {
+                    Box::new(0..height)
+                }
+            };
+            let cols = |rev| -> Box<dyn Iterator<Item = usize>> {
+                if rev {
+                    Box::new((0..width).rev())
+                } else {
+                    Box::new(0..width)
+                }
+            };
+            // The next cell must be done first, so go against the direction
+            for (dir, rev) in [(0, false), (1, true), (2, true), (3, false)] {
+                let cells = if dir % 2 == 0 {
+                    rows(rev)
+                        .flat_map(|row| (0..width).map(move |col| (row, col)))
+                        .collect::<Vec<_>>()
+                } else {
+                    cols(rev)
+                        .flat_map(|col| (0..height).map(move |row| (row, col)))
+                        .collect()
+                };
+                for (row, col) in cells {
+                    jumps[row][col][dir] = match self.next((row, col), dir) {
+                        None => None,
+                        Some((r, c)) => match self.0[r][c] {
+                            Block::Obstruction => Some((row, col)),
+                            Block::Empty(_) => jumps[r][c][dir],
+                        },
+                    };
+                }
+            }
+
+            JumpTable {
+                height,
+                width,
+                jumps,
+            }
+        }
+
+        // Every position where a new obstruction makes the guard loop, with the loop
+        pub fn loop_obstructions(&self, guard: Pos) -> Vec<(Pos, Vec<Turn>)> {
+            let mut walked = self.clone();
+            walked.guard_walk(guard).for_each(drop);
+            let table = self.jump_table();
+
+            let mut result = walked
+                .positions()
+                .filter(|(row, col, block)| {
+                    matches!(block, Block::Empty(1..)) && (*row, *col) != guard
+                })
+                .filter_map(
+                    |(row, col, _)| match table.patrol(guard, Some((row, col))) {
+                        Patrol::Loop(cycle) => Some(((row, col), cycle)),
+                        Patrol::Out(_) => None,
+                    },
+                )
+                .collect::<Vec<_>>();
+            result.sort();
+            result
+        }
+    }
+
+    impl JumpTable {
+        // `extra` is one more obstruction which is not in the table
+        fn jump(&self, (row, col): Pos, dir: usize, extra: Option<Pos>) -> Option<Pos> {
+            let stop = self.jumps[row][col][dir];
+            let Some((er, ec)) = extra else {
+                return stop;
+            };
+            // Steps from current position to the extra obstruction and the stop
+            let distance = |(r, c): Pos| -> Option<usize> {
+                match dir {
+                    0 => (c == col && r <= row).then(|| row - r),
+                    1 => (r == row && c >= col).then(|| c - col),
+                    2 => (c == col && r >= row).then(|| r - row),
+                    _ => (r == row && c <= col).then(|| col - c),
+                }
+            };
+            match distance((er, ec)) {
+                Some(to_extra)
+                    if to_extra >= 1
+                        && stop.is_none_or(|s| to_extra <= distance(s).unwrap() + 1) =>
+                {
+                    let (oy, ox) = DIRECTIONS[dir];
+                    Some((er.wrapping_add_signed(-oy), ec.wrapping_add_signed(-ox)))
+                }
+                _ => stop,
+            }
+        }
+
+        // Only turn points are visited, so it takes O(turns)
+        fn patrol(&self, guard: Pos, extra: Option<Pos>) -> Patrol {
+            debug_assert!(guard.0 < self.height && guard.1 < self.width);
+            let mut turns = vec![];
+            let mut seen = HashMap::new();
+            let (mut pos, mut dir) = (guard, 0);
+            loop {
+                let Some(stop) = self.jump(pos, dir, extra) else {
+                    return Patrol::Out(turns);
+                };
+                if let Some(idx) = seen.insert((stop, dir), turns.len()) {
+                    return Patrol::Loop(turns.split_off(idx));
+                }
+                turns.push((stop, dir));
+                (pos, dir) = (stop, (dir + 1) % 4);
+            }
+        }
+    }
+
+    fn parse(lines: impl Iterator<Item = String>) -> (Map, Pos) {
         let mut guard = (0, 0);
-        let map = read_by_line("tests/data/day6.input.txt")
+        let map = lines
             .enumerate()
             .map(|(row, line)| {
                 line.trim_end()
@@ -146,6 +293,10 @@ mod tests {
         (Map(map), guard)
     }
 
+    fn data() -> (Map, Pos) {
+        parse(read_by_line("tests/data/day6.input.txt"))
+    }
+
     #[test]
     fn part1() {
         let (mut map, guard) = data();
@@ -156,18 +307,63 @@ mod tests {
     #[test]
     fn part2() {
         let (map, guard) = data();
-        let positions = map
-            .positions()
-            .filter(|(_, _, block)| matches!(block, Block::Empty(0)));
-
-        let result = positions
-            .filter(|(y, x, _)| {
-                let mut map = map.clone();
-                map.0[*y][*x] = Block::Obstruction;
-                matches!(map.guard_walk(guard).last().unwrap(), WalkStep::Loop)
-            })
-            .count();
+        let result = map.loop_obstructions(guard).len();
 
         assert_eq!(result, 1909);
     }
+
+    const EXAMPLE: &str = "....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    fn example() -> (Map, Pos) {
+        parse(EXAMPLE.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn example_loops() {
+        let (map, guard) = example();
+        let Patrol::Out(turns) = map.jump_table().patrol(guard, None) else {
+            panic!("guard should leave");
+        };
+        assert_eq!(turns[..2], [((1, 4), 0), ((1, 8), 1)]);
+
+        let mut walked = map.clone();
+        walked.guard_walk(guard).for_each(drop);
+        assert_eq!(walked.visited_block_count(), 41);
+
+        let loops = map.loop_obstructions(guard);
+        assert_eq!(
+            loops.iter().map(|(pos, _)| *pos).collect::<Vec<_>>(),
+            [(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]
+        );
+        // Left of the guard's start, it walks around a rectangle
+        let (_, cycle) = &loops[0];
+        assert_eq!(cycle, &[((1, 4), 0), ((1, 8), 1), ((6, 8), 2), ((6, 4), 3)]);
+    }
+
+    #[test]
+    fn same_as_walking() {
+        let (map, guard) = example();
+        let table = map.jump_table();
+        for row in 0..10 {
+            for col in 0..10 {
+                if !matches!(map.0[row][col], Block::Empty(0)) {
+                    continue;
+                }
+                let mut blocked = map.clone();
+                blocked.0[row][col] = Block::Obstruction;
+                let walk_loop = matches!(blocked.guard_walk(guard).last().unwrap(), WalkStep::Loop);
+                let patrol_loop = matches!(table.patrol(guard, Some((row, col))), Patrol::Loop(_));
+                assert_eq!(walk_loop, patrol_loop, "{:?}", (row, col));
+            }
+        }
+    }
 }
@@ -15,12 +15,151 @@ mod tests {
     }
 
     trait Operator {
-        fn rollback(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>>;
+        fn symbol(&self) -> &str;
+
+        // `None` if the result is not defined or overflows
+        fn apply(&self, left: i64, right: i64) -> Option<i64>;
+
+        // Find the left side from result, `Break` means it can not be known from them,
+        // so every possible left side should be checked.
+        fn rollback(&self, _result: i64, _last: i64) -> Option<ControlFlow<(), i64>> {
+            Some(ControlFlow::Break(()))
+        }
+
+        // Bigger is evaluated first when using precedence
+        fn precedence(&self) -> u8 {
+            1
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Evaluation {
+        LeftToRight,
+        // Left associative in same level
+        Precedence,
+    }
+
+    // A sequence of operators placed between numbers
+    type Ops<'a> = Vec<&'a dyn Operator>;
+
+    struct Expression<'a> {
+        numbers: &'a [i64],
+        ops: Ops<'a>,
+    }
+
+    impl Expression<'_> {
+        fn value(&self, evaluation: Evaluation) -> Option<i64> {
+            match evaluation {
+                Evaluation::LeftToRight => left_to_right(self.numbers, &self.ops),
+                Evaluation::Precedence => with_precedence(self.numbers, &self.ops),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Expression<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.numbers[0])?;
+            self.numbers[1..]
+                .iter()
+                .zip(&self.ops)
+                .try_for_each(|(n, op)| write!(f, " {} {}", op.symbol(), n))
+        }
+    }
+
+    fn left_to_right(numbers: &[i64], ops: &[&dyn Operator]) -> Option<i64> {
+        numbers[1..]
+            .iter()
+            .zip(ops)
+            .try_fold(numbers[0], |acc, (n, op)| op.apply(acc, *n))
+    }
+
+    fn with_precedence(numbers: &[i64], ops: &[&dyn Operator]) -> Option<i64> {
+        let mut values = vec![numbers[0]];
+        let mut pending: Vec<&dyn Operator> = vec![];
+        fn reduce(values: &mut Vec<i64>, op: &dyn Operator) -> Option<()> {
+            let right = values.pop().unwrap();
+            let left = values.pop().unwrap();
+            values.push(op.apply(left, right)?);
+            Some(())
+        }
+        for (n, op) in numbers[1..].iter().zip(ops) {
+            while let Some(top) = pending.last() {
+                if top.precedence() < op.precedence() {
+                    break;
+                }
+                reduce(&mut values, pending.pop().unwrap())?;
+            }
+            pending.push(*op);
+            values.push(*n);
+        }
+        while let Some(op) = pending.pop() {
+            reduce(&mut values, op)?;
+        }
+        values.pop()
+    }
+
+    // Every operator sequence of the numbers and its value, from left to right
+    fn forward<'a>(
+        numbers: &'a [i64],
+        operators: &'a [&'a dyn Operator],
+    ) -> Box<dyn Iterator<Item = (i64, Ops<'a>)> + 'a> {
+        match numbers.split_last() {
+            None => Box::new(std::iter::empty()),
+            Some((first, [])) => Box::new(std::iter::once((*first, vec![]))),
+            Some((last, numbers)) => {
+                Box::new(forward(numbers, operators).flat_map(move |(value, ops)| {
+                    operators.iter().filter_map(move |op| {
+                        let value = op.apply(value, *last)?;
+                        let mut ops = ops.clone();
+                        ops.push(*op);
+                        Some((value, ops))
+                    })
+                }))
+            }
+        }
+    }
+
+    // Every operator sequence of `count` operators, without evaluating anything
+    fn sequences<'a>(
+        count: usize,
+        operators: &'a [&'a dyn Operator],
+    ) -> Box<dyn Iterator<Item = Ops<'a>> + 'a> {
+        if count == 0 {
+            return Box::new(std::iter::once(vec![]));
+        }
+        Box::new(sequences(count - 1, operators).flat_map(move |ops| {
+            operators.iter().map(move |op| {
+                let mut ops = ops.clone();
+                ops.push(*op);
+                ops
+            })
+        }))
     }
 
     impl Equ {
         pub fn solvable(&self, operators: &[&dyn Operator]) -> bool {
-            SolveStep::new(self).solvable(operators)
+            SolveStep::new(self).solutions(operators).next().is_some()
+        }
+
+        pub fn solutions<'a>(
+            &'a self,
+            operators: &'a [&'a dyn Operator],
+            evaluation: Evaluation,
+        ) -> Box<dyn Iterator<Item = Expression<'a>> + 'a> {
+            let numbers = self.numbers.as_slice();
+            let expression = move |ops| Expression { numbers, ops };
+            match evaluation {
+                Evaluation::LeftToRight => {
+                    Box::new(SolveStep::new(self).solutions(operators).map(expression))
+                }
+                // Partial results from left to right mean nothing here, they may even overflow
+                Evaluation::Precedence if numbers.is_empty() => Box::new(std::iter::empty()),
+                Evaluation::Precedence => Box::new(
+                    sequences(numbers.len() - 1, operators)
+                        .map(expression)
+                        .filter(|e| e.value(Evaluation::Precedence) == Some(self.result)),
+                ),
+            }
         }
     }
 
@@ -38,11 +177,6 @@ mod tests {
     }
 
     impl<'a> SolveStep<'a> {
-        const WIN: Self = Self {
-            result: 0,
-            numbers: &[],
-        };
-
         pub fn new(equ: &'a Equ) -> Self {
             Self {
                 result: equ.result,
@@ -50,39 +184,73 @@ mod tests {
             }
         }
 
-        pub fn solvable(&self, operators: &[&dyn Operator]) -> bool {
-            if self.numbers.is_empty() {
-                return self.result == 0;
+        // Roll back from the last number, only works for left to right evaluation
+        pub fn solutions(
+            self,
+            operators: &'a [&'a dyn Operator],
+        ) -> Box<dyn Iterator<Item = Ops<'a>> + 'a> {
+            let Some((last, numbers)) = self.numbers.split_last() else {
+                return Box::new(std::iter::empty());
+            };
+            if numbers.is_empty() {
+                return Box::new((*last == self.result).then(Vec::new).into_iter());
             }
 
-            let (last, numbers) = self.numbers.split_last().unwrap();
-
-            operators
-                .iter()
-                .filter_map(|op| {
-                    op.rollback(self.result, *last).map(|ctl| match ctl {
-                        ControlFlow::Break(()) => Self::WIN,
-                        ControlFlow::Continue(result) => Self { result, numbers },
-                    })
+            let result = self.result;
+            Box::new(operators.iter().flat_map(move |op| {
+                let prefixes: Box<dyn Iterator<Item = Ops<'a>>> = match op.rollback(result, *last) {
+                    None => Box::new(std::iter::empty()),
+                    Some(ControlFlow::Continue(result)) => {
+                        Self { result, numbers }.solutions(operators)
+                    }
+                    Some(ControlFlow::Break(())) => Box::new(
+                        forward(numbers, operators)
+                            .filter(move |(value, _)| op.apply(*value, *last) == Some(result))
+                            .map(|(_, ops)| ops),
+                    ),
+                };
+                prefixes.map(move |mut ops| {
+                    ops.push(*op);
+                    ops
                 })
-                .any(|x| x.solvable(operators))
+            }))
         }
     }
 
+    fn parse(lines: impl Iterator<Item = String>) -> impl Iterator<Item = Equ> {
+        lines.map(|line| line.parse().unwrap())
+    }
+
     fn data() -> impl Iterator<Item = Equ> {
-        read_by_line("tests/data/day7.input.txt").map(|line| line.parse().unwrap())
+        parse(read_by_line("tests/data/day7.input.txt"))
     }
 
     struct Add;
     impl Operator for Add {
-        fn rollback<'a>(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
-            Some(ControlFlow::Continue(result - last))
+        fn symbol(&self) -> &str {
+            "+"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_add(right)
+        }
+
+        fn rollback(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
+            result.checked_sub(last).map(ControlFlow::Continue)
         }
     }
 
     struct Multiple;
     impl Operator for Multiple {
-        fn rollback<'a>(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
+        fn symbol(&self) -> &str {
+            "*"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_mul(right)
+        }
+
+        fn rollback(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
             if last == 0 {
                 if result == 0 {
                     Some(ControlFlow::Break(()))
@@ -95,22 +263,82 @@ mod tests {
                 None
             }
         }
+
+        fn precedence(&self) -> u8 {
+            2
+        }
     }
 
     struct Join;
+    impl Join {
+        fn shift(right: i64) -> Option<i64> {
+            (right >= 0).then_some(())?;
+            10i64.checked_pow(num::digits(right as u128))
+        }
+    }
     impl Operator for Join {
-        fn rollback<'a>(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
-            // PERF: I know I can use some math there, but to_string is just more convenient...
-            let rs = result.to_string();
-            let ls = last.to_string();
-            if rs.ends_with(&ls) {
-                rs[0..rs.len() - ls.len()]
-                    .parse()
-                    .ok()
-                    .map(ControlFlow::Continue)
-            } else {
-                None
-            }
+        fn symbol(&self) -> &str {
+            "||"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_mul(Self::shift(right)?)?.checked_add(right)
+        }
+
+        fn rollback(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
+            let rest = result.checked_sub(last)?;
+            let shift = Self::shift(last)?;
+            (rest >= 0 && rest % shift == 0).then(|| ControlFlow::Continue(rest / shift))
+        }
+
+        fn precedence(&self) -> u8 {
+            4
+        }
+    }
+
+    struct Subtract;
+    impl Operator for Subtract {
+        fn symbol(&self) -> &str {
+            "-"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_sub(right)
+        }
+
+        fn rollback(&self, result: i64, last: i64) -> Option<ControlFlow<(), i64>> {
+            result.checked_add(last).map(ControlFlow::Continue)
+        }
+    }
+
+    // Rounds toward zero, so left side can not be known from the result
+    struct Divide;
+    impl Operator for Divide {
+        fn symbol(&self) -> &str {
+            "/"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_div(right)
+        }
+
+        fn precedence(&self) -> u8 {
+            2
+        }
+    }
+
+    struct Power;
+    impl Operator for Power {
+        fn symbol(&self) -> &str {
+            "^"
+        }
+
+        fn apply(&self, left: i64, right: i64) -> Option<i64> {
+            left.checked_pow(right.try_into().ok()?)
+        }
+
+        fn precedence(&self) -> u8 {
+            3
         }
     }
 
@@ -130,4 +358,102 @@ mod tests {
     fn part2() {
         assert_eq!(calculate(&[&Add, &Multiple, &Join]), 70597497486371);
     }
+
+    const EXAMPLE: &str = "190: 10 19
+3267: 81 40 27
+83: 17 5
+156: 15 6
+7290: 6 8 6 15
+161011: 16 10 13
+192: 17 8 14
+21037: 9 7 18 13
+292: 11 6 16 20";
+
+    fn example(s: &str) -> Vec<Equ> {
+        parse(s.lines().map(str::to_string)).collect()
+    }
+
+    fn all(equ: &Equ, operators: &[&dyn Operator], evaluation: Evaluation) -> Vec<String> {
+        let mut result = equ
+            .solutions(operators, evaluation)
+            .inspect(|e| assert_eq!(e.value(evaluation), Some(equ.result), "{}", e))
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn example_expressions() {
+        let equs = example(EXAMPLE);
+        let sum = |operators: &[&dyn Operator]| -> i64 {
+            equs.iter()
+                .filter(|equ| equ.solvable(operators))
+                .map(|equ| equ.result)
+                .sum()
+        };
+        assert_eq!(sum(&[&Add, &Multiple]), 3749);
+        assert_eq!(sum(&[&Add, &Multiple, &Join]), 11387);
+
+        let ops: &[&dyn Operator] = &[&Add, &Multiple, &Join];
+        assert_eq!(
+            all(&equs[1], ops, Evaluation::LeftToRight),
+            ["81 * 40 + 27", "81 + 40 * 27"]
+        );
+        assert_eq!(
+            all(&equs[4], ops, Evaluation::LeftToRight),
+            ["6 * 8 || 6 * 15"]
+        );
+        assert!(all(&equs[2], ops, Evaluation::LeftToRight).is_empty());
+
+        // 81 + 40 * 27 is 1161 now
+        assert_eq!(all(&equs[1], ops, Evaluation::Precedence), ["81 * 40 + 27"]);
+        // Join goes first, 6 * 86 * 15
+        let e = equs[4]
+            .solutions(ops, Evaluation::LeftToRight)
+            .next()
+            .unwrap();
+        assert_eq!(e.value(Evaluation::Precedence), Some(7740));
+        assert!(all(&equs[4], ops, Evaluation::Precedence).is_empty());
+
+        // Overflows from left to right, but not with precedence
+        let ops: &[&dyn Operator] = &[&Add, &Multiple];
+        let equ = &example("1000000000000000000: 1000000000000000000 0 100")[0];
+        assert_eq!(
+            all(equ, ops, Evaluation::Precedence),
+            ["1000000000000000000 + 0 * 100"]
+        );
+        assert!(all(equ, ops, Evaluation::LeftToRight).is_empty());
+    }
+
+    #[test]
+    fn extra_operators() {
+        let ops: &[&dyn Operator] = &[&Add, &Subtract, &Multiple, &Divide, &Power];
+        let solve = |s: &str, evaluation| all(&example(s)[0], ops, evaluation);
+
+        assert_eq!(
+            solve("7: 2 3 1", Evaluation::LeftToRight),
+            ["2 * 3 + 1", "2 ^ 3 - 1"]
+        );
+        assert_eq!(solve("5: 11 2", Evaluation::LeftToRight), ["11 / 2"]);
+        assert_eq!(solve("2: 100 7 7", Evaluation::Precedence), ["100 / 7 / 7"]);
+        assert!(solve("51: 100 7 7", Evaluation::LeftToRight).is_empty());
+        assert_eq!(
+            solve("51: 100 7 7", Evaluation::Precedence),
+            ["100 - 7 * 7"]
+        );
+
+        // Overflow is not a solution
+        let ops: &[&dyn Operator] = &[&Add, &Multiple, &Subtract];
+        assert_eq!(
+            all(
+                &example("0: 9223372036854775807 1 0")[0],
+                ops,
+                Evaluation::LeftToRight
+            ),
+            ["9223372036854775807 * 1 * 0", "9223372036854775807 - 1 * 0"]
+        );
+        assert!(!example("1: 922337203685477580 10")[0].solvable(&[&Join]));
+        assert!(example("92233720368547758: 9223372036 854775 8")[0].solvable(&[&Join]));
+    }
 }
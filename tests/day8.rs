@@ -4,159 +4,327 @@ mod tests {
 
     use advent2024::*;
 
-    type Pos = (isize, isize);
+    type Point<const N: usize> = [isize; N];
 
-    struct Pair(Pos, Pos);
+    type Pos = Point<2>;
 
-    impl Pair {
-        pub fn antinode(&self) -> [Pos; 2] {
-            let Pair((ay, ax), (by, bx)) = self;
-            let (dy, dx) = (by - ay, bx - ax);
-            [(ay - dy, ax - dx), (by + dy, bx + dx)]
+    // Every axis goes from 0 to (not include) its size
+    struct Bounds<const N: usize>(Point<N>);
+
+    impl<const N: usize> Bounds<N> {
+        fn contains(&self, p: &Point<N>) -> bool {
+            p.iter().zip(self.0).all(|(x, size)| (0..size).contains(x))
         }
 
-        // I can't believe Rust do not have gcd in std....................
-        fn gcd(mut n: isize, mut m: isize) -> isize {
-            assert!(n != 0 && m != 0);
-            while m != 0 {
-                if m < n {
-                    std::mem::swap(&mut m, &mut n);
+        // All `k` that `start + k * d` is inside
+        fn steps(&self, start: &Point<N>, d: &Point<N>) -> Option<(isize, isize)> {
+            let mut range = (isize::MIN, isize::MAX);
+            for i in 0..N {
+                let (a, d, size) = (start[i], d[i], self.0[i]);
+                if d == 0 {
+                    (0..size).contains(&a).then_some(())?;
+                    continue;
                 }
-                m %= n;
+                // 0 <= a + k * d <= size - 1
+                let (lo, hi) = if d > 0 {
+                    (div_ceil(-a, d), div_floor(size - 1 - a, d))
+                } else {
+                    (div_ceil(size - 1 - a, d), div_floor(-a, d))
+                };
+                range = (range.0.max(lo), range.1.min(hi));
             }
-            n
+            (range.0 <= range.1).then_some(range)
         }
+    }
 
-        pub fn resonant_antinode(&self) -> [impl Iterator<Item = Pos>; 2] {
-            struct Antinode {
-                start: Pos,
-                d: Pos,
-                multiplier: isize,
-                step: isize,
-            }
+    fn div_floor(a: isize, b: isize) -> isize {
+        if b < 0 {
+            (-a).div_euclid(-b)
+        } else {
+            a.div_euclid(b)
+        }
+    }
 
-            impl Iterator for Antinode {
-                type Item = Pos;
+    fn div_ceil(a: isize, b: isize) -> isize {
+        -div_floor(-a, b)
+    }
 
-                fn next(&mut self) -> Option<Self::Item> {
-                    let (ay, ax) = self.start;
-                    let (dy, dx) = self.d;
-                    let pos = (ay + self.multiplier * dy, ax + self.multiplier * dx);
-                    self.multiplier += self.step;
-                    Some(pos)
-                }
-            }
+    #[derive(Debug, Clone, Copy)]
+    enum Rule {
+        // One antenna is `far / near` times as far as the other, `interior` also
+        // includes points between the two antennas.
+        Ratio {
+            far: isize,
+            near: isize,
+            interior: bool,
+        },
+        // Every grid point on the line
+        Resonant,
+    }
 
-            let Pair((ay, ax), (by, bx)) = *self;
-            let (mut dy, mut dx) = (by - ay, bx - ax);
+    struct Pair<const N: usize>(Point<N>, Point<N>);
 
-            if dy == 0 {
-                dx = 1
-            } else if dx == 0 {
-                dy = 1
-            } else {
-                let n = Self::gcd(dx.abs(), dy.abs());
-                dy /= n;
-                dx /= n;
+    impl<const N: usize> Pair<N> {
+        fn delta(&self) -> Point<N> {
+            std::array::from_fn(|i| self.1[i] - self.0[i])
+        }
+
+        // `a + t * (b - a)` with `t = num / den`, only grid points
+        fn at(&self, num: isize, den: isize) -> Option<Point<N>> {
+            let d = self.delta();
+            let mut result = self.0;
+            for i in 0..N {
+                let x = d[i] * num;
+                (x % den == 0).then_some(())?;
+                result[i] += x / den;
             }
+            Some(result)
+        }
 
-            [
-                Antinode {
-                    start: (ay, ax),
-                    d: (dy, dx),
-                    multiplier: 0,
-                    step: 1,
-                },
-                Antinode {
-                    start: (ay, ax),
-                    d: (dy, dx),
-                    multiplier: 0,
-                    step: -1,
-                },
-            ]
+        pub fn antinodes(&self, rule: Rule, bounds: &Bounds<N>) -> Vec<Point<N>> {
+            match rule {
+                Rule::Ratio {
+                    far,
+                    near,
+                    interior,
+                } => {
+                    assert!(far > 0 && near > 0, "ratio must be positive");
+                    // |t| / |t - 1| = x / y
+                    let mut ts = vec![];
+                    for (x, y) in [(far, near), (near, far)] {
+                        if x != y {
+                            ts.push((x, x - y));
+                        }
+                        if interior {
+                            ts.push((x, x + y));
+                        }
+                    }
+                    let mut result = ts
+                        .into_iter()
+                        .filter_map(|(num, den)| self.at(num, den))
+                        .filter(|p| bounds.contains(p))
+                        .collect::<Vec<_>>();
+                    result.sort();
+                    result.dedup();
+                    result
+                }
+                Rule::Resonant => self.resonant(bounds).collect(),
+            }
+        }
+
+        pub fn resonant(&self, bounds: &Bounds<N>) -> impl Iterator<Item = Point<N>> {
+            let d = self.delta();
+            let g = d.iter().fold(0, |g, x| num::gcd(g, *x).unwrap());
+            let d = d.map(|x| if g == 0 { 0 } else { x / g });
+            let start = self.0;
+            // Antennas at the same point have no line, only the point itself
+            let (lo, hi) = if g == 0 {
+                if bounds.contains(&start) {
+                    (0, 0)
+                } else {
+                    (1, 0)
+                }
+            } else {
+                bounds.steps(&start, &d).unwrap_or((1, 0))
+            };
+            (lo..=hi).map(move |k| std::array::from_fn(|i| start[i] + k * d[i]))
         }
     }
 
     #[derive(Debug)]
-    struct Map {
-        antennas: HashMap<u8, Vec<Pos>>,
-    }
-
-    impl FromIterator<(u8, Pos)> for Map {
-        fn from_iter<T: IntoIterator<Item = (u8, Pos)>>(iter: T) -> Self {
-            let iter = iter.into_iter();
-            let mut map = HashMap::with_capacity(36);
-            iter.for_each(|(ty, pos)| {
-                map.entry(ty)
-                    .or_insert_with(|| Vec::with_capacity(128))
-                    .push(pos);
-            });
-            Self { antennas: map }
+    struct Map<const N: usize> {
+        antennas: HashMap<u8, Vec<Point<N>>>,
+    }
+
+    impl<const N: usize> FromIterator<(u8, Point<N>)> for Map<N> {
+        fn from_iter<T: IntoIterator<Item = (u8, Point<N>)>>(iter: T) -> Self {
+            Self {
+                antennas: group(iter),
+            }
         }
     }
 
-    impl Map {
-        pub fn pairs(&self) -> impl Iterator<Item = Pair> + '_ {
-            self.antennas
-                .values()
-                .flat_map(|locations| {
-                    locations.iter().enumerate().flat_map(|(idx, pos1)| {
-                        locations.iter().skip(idx + 1).map(move |pos2| (pos1, pos2))
-                    })
+    impl<const N: usize> Map<N> {
+        pub fn pairs(&self) -> impl Iterator<Item = (u8, Pair<N>)> + '_ {
+            self.antennas.iter().flat_map(|(ty, locations)| {
+                locations.iter().enumerate().flat_map(move |(idx, pos1)| {
+                    locations
+                        .iter()
+                        .skip(idx + 1)
+                        .map(move |pos2| (*ty, Pair(*pos1, *pos2)))
                 })
-                .map(|(a, b)| Pair(*a, *b))
+            })
+        }
+
+        // Antinodes of every frequency
+        pub fn report(&self, rule: Rule, bounds: &Bounds<N>) -> HashMap<u8, HashSet<Point<N>>> {
+            let mut result: HashMap<_, HashSet<_>> = HashMap::new();
+            self.pairs().for_each(|(ty, pair)| {
+                result
+                    .entry(ty)
+                    .or_default()
+                    .extend(pair.antinodes(rule, bounds))
+            });
+            result
+        }
+
+        pub fn antinodes(&self, rule: Rule, bounds: &Bounds<N>) -> HashSet<Point<N>> {
+            self.pairs()
+                .flat_map(|(_, pair)| pair.antinodes(rule, bounds))
+                .collect()
         }
     }
 
-    fn data() -> (Map, Pos /* Size of Map */) {
-        let mut size = (0, 0);
-        let map = read_by_line("tests/data/day8.input.txt")
+    const PART1: Rule = Rule::Ratio {
+        far: 2,
+        near: 1,
+        interior: false,
+    };
+
+    fn parse(lines: impl Iterator<Item = String>) -> (Map<2>, Bounds<2>) {
+        let mut size = [0, 0];
+        let map = lines
             .enumerate()
             .inspect(|(row, line)| {
-                size = (
-                    size.0.max(*row as isize + 1),
-                    size.1.max(line.trim_end().len() as isize),
-                )
+                size = [
+                    size[0].max(*row as isize + 1),
+                    size[1].max(line.trim_end().len() as isize),
+                ]
             })
             .flat_map(|(row, line)| {
                 line.into_bytes()
                     .into_iter()
                     .enumerate()
                     .filter(|(_, ty)| !matches!(ty, b'\r' | b'\n' | b'.'))
-                    .map(move |(col, ty)| (ty, (row as isize, col as isize)))
+                    .map(move |(col, ty)| (ty, [row as isize, col as isize]))
             })
             .collect();
-        (map, size)
+        (map, Bounds(size))
     }
 
-    fn check((py, px): &Pos, (sy, sx): &Pos) -> bool {
-        *px >= 0 && *py >= 0 && px < sx && py < sy
+    fn data() -> (Map<2>, Bounds<2>) {
+        parse(read_by_line("tests/data/day8.input.txt"))
     }
 
     #[test]
     fn part1() {
-        let (map, size) = data();
-        let locations: HashSet<Pos> = map
-            .pairs()
-            .flat_map(|pair| pair.antinode())
-            .filter(|pos| check(pos, &size))
-            .collect();
+        let (map, bounds) = data();
+        let locations: HashSet<Pos> = map.antinodes(PART1, &bounds);
 
         assert_eq!(locations.len(), 367);
     }
 
     #[test]
     fn part2() {
-        let (map, size) = data();
-        let locations: HashSet<Pos> = map
-            .pairs()
-            .flat_map(|x| {
-                let [pl, pr] = x.resonant_antinode();
-                pl.take_while(|pos| check(pos, &size))
-                    .chain(pr.take_while(|pos| check(pos, &size)))
-            })
-            .collect();
+        let (map, bounds) = data();
+        let locations: HashSet<Pos> = map.antinodes(Rule::Resonant, &bounds);
 
         assert_eq!(locations.len(), 1285);
     }
+
+    const EXAMPLE: &str = "............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............";
+
+    fn example(s: &str) -> (Map<2>, Bounds<2>) {
+        parse(s.lines().map(str::to_string))
+    }
+
+    #[test]
+    fn example_antinodes() {
+        let (map, bounds) = example(EXAMPLE);
+        assert_eq!(map.antinodes(PART1, &bounds).len(), 14);
+        assert_eq!(map.antinodes(Rule::Resonant, &bounds).len(), 34);
+
+        let report = map.report(PART1, &bounds);
+        assert_eq!(report[&b'A'].len(), 5);
+        assert_eq!(report[&b'0'].len(), 10);
+        // One location has antinodes of both
+        assert_eq!(report[&b'A'].intersection(&report[&b'0']).count(), 1);
+
+        let (map, bounds) = example("T.........\n...T......\n.T........");
+        let bounds = Bounds([10, bounds.0[1]]);
+        assert_eq!(map.antinodes(Rule::Resonant, &bounds).len(), 9);
+    }
+
+    #[test]
+    fn ratios() {
+        let pair = Pair([0, 0], [3, 3]);
+        let bounds = Bounds([10, 10]);
+        let ratio = |far, near, interior| {
+            pair.antinodes(
+                Rule::Ratio {
+                    far,
+                    near,
+                    interior,
+                },
+                &bounds,
+            )
+        };
+        assert_eq!(ratio(2, 1, false), [[6, 6]]);
+        assert_eq!(ratio(2, 1, true), [[1, 1], [2, 2], [6, 6]]);
+        // Not on the grid
+        assert!(ratio(3, 1, true).is_empty());
+        assert!(ratio(1, 1, true).is_empty());
+        assert_eq!(
+            Pair([1, 1], [3, 3]).antinodes(
+                Rule::Ratio {
+                    far: 1,
+                    near: 1,
+                    interior: true
+                },
+                &bounds
+            ),
+            [[2, 2]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ratio must be positive")]
+    fn zero_ratio() {
+        let rule = Rule::Ratio {
+            far: 0,
+            near: 0,
+            interior: true,
+        };
+        Pair([0, 0], [3, 3]).antinodes(rule, &Bounds([10, 10]));
+    }
+
+    #[test]
+    fn resonant_lines() {
+        let bounds = Bounds([10, 10]);
+        // Not a unit vector, but still on the same row
+        let row = Pair([3, 2], [3, 6]).resonant(&bounds).collect::<Vec<_>>();
+        assert_eq!(row.len(), 10);
+        assert!(row.iter().all(|p| p[0] == 3));
+
+        let points = Pair([2, 4], [4, 8]).resonant(&bounds).collect::<Vec<_>>();
+        assert_eq!(points, [[0, 0], [1, 2], [2, 4], [3, 6], [4, 8]]);
+
+        let cube = Bounds([10, 10, 10]);
+        let points = Pair([0, 0, 0], [2, 4, 6])
+            .resonant(&cube)
+            .collect::<Vec<_>>();
+        assert_eq!(points, [[0, 0, 0], [1, 2, 3], [2, 4, 6], [3, 6, 9]]);
+        let points = Pair([5, 5, 5], [5, 5, 6]).resonant(&cube).count();
+        assert_eq!(points, 10);
+        assert_eq!(
+            Pair([0, 0, 0], [1, 1, 1]).antinodes(PART1, &cube),
+            [[2, 2, 2]]
+        );
+
+        // Same point twice
+        let points = Pair([1, 1], [1, 1]).resonant(&bounds).collect::<Vec<_>>();
+        assert_eq!(points, [[1, 1]]);
+        assert_eq!(Pair([11, 1], [11, 1]).resonant(&bounds).count(), 0);
+    }
 }
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::{cmp::Reverse, collections::BinaryHeap, fmt::Write};
+
     use advent2024::*;
 
     type FileID = usize;
@@ -17,7 +19,7 @@ mod tests {
 
     impl DiskMap {
         fn get(&self, index: usize) -> Option<Fragment> {
-            let block = if index % 2 == 0 {
+            let block = if index.is_multiple_of(2) {
                 Block::File(index / 2)
             } else {
                 Block::Empty
@@ -167,27 +169,64 @@ mod tests {
     }
 
     impl ContiguousDiskMap {
+        // Free spans are indexed by their length, each length has a min-heap of positions,
+        // so finding the leftmost span which fits is one heap peek per length.
         fn defrag_whole_file(&mut self) {
-            let disk = &mut self.0;
-            let mut idx = disk.len();
-            while let Some(current) = idx.checked_sub(1) {
-                let (block, count) = disk[current];
-                if let Block::File(file) = block {
-                    let free = disk.iter().take(idx).copied().enumerate().find(
-                        |(_, (block, free_count))| {
-                            matches!(block, Block::Empty) && *free_count >= count
-                        },
-                    );
-                    if let Some((free_idx, (_, free_count))) = free {
-                        disk[free_idx] = (Block::File(file), count);
-                        disk[current].0 = Block::Empty;
-                        if free_count > count {
-                            disk.insert(free_idx + 1, (Block::Empty, free_count - count));
-                        }
+            let mut files = vec![];
+            let mut gaps = vec![];
+            let mut pos = 0;
+            for (block, count) in self.0.iter().copied() {
+                match block {
+                    Block::File(file) => files.push((pos, file, count)),
+                    // Next to the previous gap, it is the same span
+                    Block::Empty => match gaps.last_mut() {
+                        Some((at, size)) if *at + *size == pos => *size += count as usize,
+                        _ => gaps.push((pos, count as usize)),
+                    },
+                }
+                pos += count as usize;
+            }
+            let largest = gaps.iter().map(|(_, size)| *size).max().unwrap_or(0);
+            let mut free: Vec<BinaryHeap<Reverse<usize>>> = vec![BinaryHeap::new(); largest + 1];
+            gaps.into_iter()
+                .filter(|(_, size)| *size > 0)
+                .for_each(|(at, size)| free[size].push(Reverse(at)));
+            let len = pos;
+
+            // Freed space is never used, since files on its left only move to left
+            for (pos, _, count) in files.iter_mut().rev() {
+                let best = (*count as usize..free.len())
+                    .filter_map(|size| free[size].peek().map(|Reverse(at)| (*at, size)))
+                    .filter(|(at, _)| at < pos)
+                    .min();
+                if let Some((at, size)) = best {
+                    free[size].pop();
+                    let rest = size - *count as usize;
+                    if rest > 0 {
+                        free[rest].push(Reverse(at + *count as usize));
                     }
+                    *pos = at;
                 }
-                idx = current;
             }
+
+            files.sort_unstable();
+            let mut disk = Vec::with_capacity(files.len() * 2);
+            // Merged gaps can be longer than a fragment holds, split them
+            let push_empty = |disk: &mut Vec<Fragment>, mut gap: usize| {
+                while gap > 0 {
+                    let count = gap.min(u8::MAX as usize);
+                    disk.push((Block::Empty, count as u8));
+                    gap -= count;
+                }
+            };
+            let mut end = 0;
+            for (pos, file, count) in files {
+                push_empty(&mut disk, pos - end);
+                disk.push((Block::File(file), count));
+                end = pos + count as usize;
+            }
+            push_empty(&mut disk, len - end);
+            self.0 = disk;
         }
 
         fn checksum(&self) -> usize {
@@ -207,15 +246,33 @@ mod tests {
         }
     }
 
-    fn data() -> DiskMap {
+    // Layout like `00...111...2`, only the last digit of file ID is shown
+    impl std::fmt::Display for ContiguousDiskMap {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for (block, count) in &self.0 {
+                let c = match block {
+                    Block::File(file) => (b'0' + (file % 10) as u8) as char,
+                    Block::Empty => '.',
+                };
+                (0..*count).try_for_each(|_| f.write_char(c))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn parse(bytes: impl Iterator<Item = u8>) -> DiskMap {
         DiskMap(
-            read_by_byte("tests/data/day9.input.txt")
+            bytes
                 .filter(|b| b.is_ascii_digit())
                 .map(|x| x - b'0')
                 .collect(),
         )
     }
 
+    fn data() -> DiskMap {
+        parse(read_by_byte("tests/data/day9.input.txt"))
+    }
+
     #[test]
     fn part1() {
         let disk: ContiguousDiskMap = data().defrag().collect();
@@ -228,4 +285,123 @@ mod tests {
         disk.defrag_whole_file();
         assert_eq!(disk.checksum(), 6250605700557);
     }
+
+    #[test]
+    fn example_layout() {
+        let disk = parse("12345".bytes());
+        let before: ContiguousDiskMap = disk.fragments().collect();
+        assert_eq!(before.to_string(), "0..111....22222");
+        let after: ContiguousDiskMap = disk.defrag().collect();
+        assert_eq!(after.to_string(), "022111222");
+
+        let disk = parse("2333133121414131402".bytes());
+        let mut whole: ContiguousDiskMap = disk.fragments().collect();
+        assert_eq!(
+            whole.to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        whole.defrag_whole_file();
+        assert_eq!(
+            whole.to_string(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(whole.checksum(), 2858);
+
+        let after: ContiguousDiskMap = disk.defrag().collect();
+        assert_eq!(after.to_string(), "0099811188827773336446555566");
+        assert_eq!(after.checksum(), 1928);
+    }
+
+    #[test]
+    fn whole_file_gaps() {
+        let defrag = |s: &str| {
+            let mut whole: ContiguousDiskMap = parse(s.bytes()).fragments().collect();
+            whole.defrag_whole_file();
+            whole.to_string()
+        };
+        // Leftmost gap wins, even a bigger one, and the rest of it can still be used
+        assert_eq!(defrag("1213391"), format!("031.222{}", ".".repeat(13)));
+        // Too big to move
+        assert_eq!(defrag("1192"), "0.111111111..");
+
+        // Output of a defrag has merged gaps longer than any digit, defrag it again
+        let mut whole: ContiguousDiskMap = parse("1213391".bytes()).fragments().collect();
+        whole.defrag_whole_file();
+        let once = whole.to_string();
+        whole.defrag_whole_file();
+        assert_eq!(whole.to_string(), once);
+        // Files can move further in the second one, but nothing is lost, and then it is stable
+        let mut whole: ContiguousDiskMap =
+            parse("2333133121414131402".bytes()).fragments().collect();
+        let sorted = |s: String| {
+            let mut s = s.into_bytes();
+            s.sort();
+            s
+        };
+        let before = sorted(whole.to_string());
+        whole.defrag_whole_file();
+        whole.defrag_whole_file();
+        let twice = whole.to_string();
+        assert_eq!(sorted(twice.clone()), before);
+        whole.defrag_whole_file();
+        assert_eq!(whole.to_string(), twice);
+    }
+
+    // Block by block, moves every file to the leftmost gap which fits
+    fn brute_force_whole_file(disk: &DiskMap) -> usize {
+        let mut blocks = disk
+            .fragments()
+            .flat_map(|(block, count)| std::iter::repeat_n(block, count as usize))
+            .collect::<Vec<_>>();
+        for file in (0..disk.0.len().div_ceil(2)).rev() {
+            let Some(start) = blocks.iter().position(|b| *b == Block::File(file)) else {
+                continue;
+            };
+            let count = disk.0[file * 2] as usize;
+            let mut run = 0;
+            for at in 0..start {
+                run = if blocks[at] == Block::Empty {
+                    run + 1
+                } else {
+                    0
+                };
+                if run == count {
+                    blocks[at + 1 - count..=at].fill(Block::File(file));
+                    blocks[start..start + count].fill(Block::Empty);
+                    break;
+                }
+            }
+        }
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| match b {
+                Block::File(file) => i * file,
+                Block::Empty => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn long_gaps() {
+        // xorshift, files are big and gaps small, so big merged gaps appear at the end
+        let mut state = 0x2545f4914f6cdd1du64;
+        let input = (0..5000)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let digit = (state % 10) as u8;
+                b'0' + if i % 2 == 0 { digit.max(1) } else { digit }
+            })
+            .collect::<Vec<_>>();
+        let disk = parse(input.into_iter());
+        let mut whole: ContiguousDiskMap = disk.fragments().collect();
+        whole.defrag_whole_file();
+        assert!(whole
+            .0
+            .iter()
+            .any(|(block, count)| *block == Block::Empty && *count == u8::MAX));
+        assert_eq!(whole.checksum(), brute_force_whole_file(&disk));
+    }
 }
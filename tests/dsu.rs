@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use advent2024::dsu::*;
+
+    #[test]
+    fn disjoint_set() {
+        let mut set = DisjointSet::new(5);
+        assert_eq!(set.count(), 5);
+        assert!(set.union(0, 1));
+        assert!(set.union(3, 4));
+        assert!(!set.union(1, 0));
+        assert!(set.same(0, 1));
+        assert!(!set.same(1, 3));
+        assert_eq!(set.count(), 3);
+
+        let new = set.push();
+        assert_eq!(new, 5);
+        assert!(set.union(new, 4));
+        assert!(set.union(2, 0));
+        assert_eq!(set.size_of(3), 3);
+        assert_eq!(set.size_of(1), 3);
+        assert_eq!(set.count(), 2);
+        assert_eq!(set.len(), 6);
+    }
+
+    #[test]
+    fn components() {
+        let grid = ["AAB", "ABB", "CAA"].map(|s| s.bytes().collect::<Vec<_>>());
+        let grid = grid.to_vec();
+
+        let same = Components::label(&grid, |a, b| a == b);
+        assert_eq!(same.count(), 4);
+        assert_eq!(same.get((0, 0)), 0);
+        assert_eq!(same.get((1, 1)), 1);
+        assert_eq!(same.get((2, 0)), 2);
+        assert_eq!(same.get((2, 2)), 3);
+        assert_eq!(
+            same.groups(),
+            [
+                vec![(0, 0), (0, 1), (1, 0)],
+                vec![(0, 2), (1, 1), (1, 2)],
+                vec![(2, 0)],
+                vec![(2, 1), (2, 2)],
+            ]
+        );
+
+        // Everything but C is open
+        let open = Components::label(&grid, |a, b| a != b'C' && b != b'C');
+        assert_eq!(open.count(), 2);
+        assert_eq!(open.get((0, 0)), open.get((2, 2)));
+    }
+}
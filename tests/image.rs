@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use advent2024::image::*;
+
+    #[test]
+    fn binary_formats() {
+        let grid = vec![b"#..#.#.##".to_vec(), b".........".to_vec()];
+
+        let mut pbm = vec![];
+        Image::from_grid(&grid, |c| c == b'#')
+            .write(&mut pbm)
+            .unwrap();
+        assert_eq!(pbm, b"P4\n9 2\n\x95\x80\x00\x00");
+
+        let mut pgm = vec![];
+        Image::from_grid(&grid[..1], |c| c).write(&mut pgm).unwrap();
+        assert_eq!(pgm, b"P5\n9 1\n255\n#..#.#.##");
+
+        let mut ppm = vec![];
+        Image::from_points((1, 2), [(0, 1), (5, 5)], (0, 0, 0), (1, 2, 3))
+            .write(&mut ppm)
+            .unwrap();
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x00\x00\x00\x01\x02\x03");
+    }
+
+    #[test]
+    fn upscale() {
+        let image = Image::from_points((2, 3), [(1, 2)], 0u8, 9);
+        let large = image.upscale(2);
+        assert_eq!(large.size(), (4, 6));
+        assert_eq!(
+            (0..6)
+                .map(|col| large.get((3, col)).unwrap())
+                .collect::<Vec<_>>(),
+            [0, 0, 0, 0, 9, 9]
+        );
+        assert_eq!(large.get((2, 4)), Some(9));
+        assert_eq!(large.get((1, 4)), Some(0));
+        assert_eq!(large.get((4, 0)), None);
+    }
+
+    #[test]
+    fn frame_sequence() {
+        let dir = std::env::temp_dir().join("advent2024-image-frames");
+        let mut frames = FrameSequence::new(&dir, "frame", 3).unwrap().start_at(9);
+        let first = frames.push(&Image::new((1, 1), true)).unwrap();
+        let second = frames.push(&Image::new((1, 1), (0, 0, 0))).unwrap();
+
+        assert_eq!(first, dir.join("frame009.pbm"));
+        assert_eq!(second, dir.join("frame010.ppm"));
+        assert_eq!(std::fs::read(first).unwrap(), b"P4\n1 1\n\x80");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "ragged grid")]
+    fn ragged_grid() {
+        let grid = vec![b"###".to_vec(), b"#".to_vec()];
+        Image::from_grid(&grid, |c| c == b'#');
+    }
+}
@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use advent2024::num::*;
+
+    // xorshift, good enough for picking test cases
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, lo: i128, hi: i128) -> i128 {
+            lo + (self.next() as i128).rem_euclid(hi - lo + 1)
+        }
+    }
+
+    #[test]
+    fn gcd_lcm() {
+        assert_eq!(gcd(12, 18), Some(6));
+        assert_eq!(gcd(-12, 18), Some(6));
+        assert_eq!(gcd(0u8, 0), Some(0));
+        assert_eq!(gcd(0, -7), Some(7));
+        assert_eq!(gcd(i64::MIN, 0), None);
+        assert_eq!(gcd(i64::MIN, -1), Some(1));
+        assert_eq!(gcd(u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(-4, 6), Some(12));
+        assert_eq!(lcm(0, 6), Some(0));
+        assert_eq!(lcm(200u8, 3), None);
+        assert_eq!(lcm(101usize, 103), Some(10403));
+
+        let mut rng = Rng(7);
+        for _ in 0..1000 {
+            let (a, b) = (rng.range(-1000, 1000) as i64, rng.range(-1000, 1000) as i64);
+            let g = gcd(a, b).unwrap();
+            let brute = (1..=a.abs().max(b.abs()))
+                .rev()
+                .find(|d| a % d == 0 && b % d == 0)
+                .unwrap_or(0);
+            assert_eq!(g, brute, "{} {}", a, b);
+            if g != 0 {
+                assert_eq!(lcm(a, b).unwrap() * g, (a * b).abs());
+            }
+        }
+    }
+
+    #[test]
+    fn ext_gcd_and_inverse() {
+        assert_eq!(ext_gcd(i128::MIN, 0), None);
+        assert_eq!(mod_inv(3, 7), Some(5));
+        assert_eq!(mod_inv(-3, 7), Some(2));
+        assert_eq!(mod_inv(4, 8), None);
+        assert_eq!(mod_inv(5, 1), Some(0));
+
+        let mut rng = Rng(11);
+        for _ in 0..1000 {
+            let big = 1 << rng.range(1, 60);
+            let (a, b) = (rng.range(-big, big), rng.range(-big, big));
+            let (g, x, y) = ext_gcd(a, b).unwrap();
+            assert_eq!(g, gcd(a, b).unwrap());
+            assert_eq!(a * x + b * y, g);
+
+            let m = rng.range(1, 1 << 60);
+            match mod_inv(a, m) {
+                Some(inv) => {
+                    assert!((0..m).contains(&inv));
+                    assert_eq!((a.rem_euclid(m) * inv) % m, 1 % m);
+                }
+                None => assert_ne!(gcd(a, m), Some(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn chinese_remainder() {
+        assert_eq!(crt([(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt([(1, 4), (3, 6)]), Some((9, 12)));
+        assert_eq!(crt([(1, 4), (2, 6)]), None);
+        assert_eq!(crt([]), Some((0, 1)));
+        assert_eq!(crt([(-1, 101), (5, 103)]), Some((10099, 10403)));
+        assert_eq!(crt([(0, 1 << 100), (0, 3 << 100)]), Some((0, 3 << 100)));
+        assert_eq!(crt([(0, 1 << 100), (0, (1 << 100) - 1)]), None);
+
+        let mut rng = Rng(13);
+        for _ in 0..1000 {
+            let (m, n) = (rng.range(1, 30), rng.range(1, 30));
+            let (a, b) = (rng.range(-50, 50), rng.range(-50, 50));
+            let brute = (0..m * n).find(|x| (x - a) % m == 0 && (x - b) % n == 0);
+            let result = crt([(a, m), (b, n)]);
+            assert_eq!(result.map(|(x, _)| x), brute, "{} {} {} {}", a, m, b, n);
+            if let Some((_, l)) = result {
+                assert_eq!(Some(l), lcm(m, n));
+            }
+        }
+    }
+
+    #[test]
+    fn pow_sqrt_digits() {
+        assert_eq!(mod_pow(2, 10, 1000), 24);
+        assert_eq!(mod_pow(5, 0, 1), 0);
+        assert_eq!(mod_pow(u64::MAX, u64::MAX, u64::MAX - 1), 1);
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(digits(0), 1);
+        assert_eq!(digits(9), 1);
+        assert_eq!(digits(10), 2);
+        assert_eq!(digits(u128::MAX), 39);
+
+        let mut rng = Rng(17);
+        for _ in 0..1000 {
+            let (base, exp, m) = (rng.next(), rng.range(0, 50) as u64, rng.next() | 1);
+            let brute = (0..exp).fold(1 % m as u128, |acc, _| acc * base as u128 % m as u128);
+            assert_eq!(mod_pow(base, exp, m) as u128, brute);
+
+            let n = (rng.next() as u128) << rng.range(0, 64);
+            let r = isqrt(n);
+            assert!(r * r <= n && (r + 1).checked_mul(r + 1).is_none_or(|x| x > n));
+
+            let n = rng.next() >> rng.range(0, 63);
+            assert_eq!(digits(n as u128) as usize, n.to_string().len());
+        }
+    }
+}